@@ -0,0 +1,38 @@
+//! Writes `precache.js` into a build of the app, listing its files for the service worker.
+//! Run by trunk after every build, in the directory given by `TRUNK_STAGING_DIR`.
+
+use std::path::Path;
+use std::{env, fs, io};
+
+use wasm_creole_service_worker::Precache;
+
+fn files(root: &Path, dir: &Path, rst: &mut Vec<(String, Vec<u8>)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files(root, &path, rst)?;
+        } else {
+            let relative = path.strip_prefix(root).expect("file out of the build");
+            let name = relative.components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            rst.push((name, fs::read(&path)?));
+        }
+    }
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let root = env::args().nth(1)
+        .or_else(|| env::var("TRUNK_STAGING_DIR").ok())
+        .expect("no build directory given");
+    let root = Path::new(&root);
+    let mut list = vec![];
+    files(root, root, &mut list)?;
+    let precache = Precache::new(&list);
+    let json = serde_json::to_string(&precache).expect("could not serialize manifest");
+    fs::write(root.join("precache.js"), format!("self.PRECACHE = {json};\n"))?;
+    println!("precached {} files as {}", precache.files.len(), precache.cache_name());
+    Ok(())
+}
@@ -1,17 +1,172 @@
-use wasm_bindgen::prelude::*;
-use web_sys::ServiceWorker;
-// When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
-// allocator.
-#[cfg(feature = "wee_alloc")]
-#[global_allocator]
-static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
-
-#[wasm_bindgen]
-pub fn run_app() -> Result<(), JsValue> {
-    // wasm_logger::init(wasm_logger::Config::default());
-    let sw = ;//ServiceWorker::
-    sw.add_event_listener_with_callback("install", |e : ex| {
-      e.wait_until()
-    });
-    Ok(())
-}
\ No newline at end of file
+//! Service worker of the editor, keeping the app available offline.
+//! `static/service-worker.js` forwards its events to the handlers here.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{Cache, ExtendableEvent, ExtendableMessageEvent, FetchEvent, Request, RequestMode, Response, ServiceWorkerGlobalScope};
+
+// When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
+// allocator.
+#[cfg(feature = "wee_alloc")]
+#[global_allocator]
+static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
+
+/// Start of the names of every cache of the app, followed by the build version.
+const CACHE_PREFIX: &str = "wcle-";
+/// Page served for navigations while offline, which routes every path itself.
+const FALLBACK: &str = "/index.html";
+/// Message of the app asking a waiting worker to take over, once the user chose to reload.
+const SKIP_WAITING: &str = "skip-waiting";
+/// Start of the names of the script and module trunk builds of this crate.
+const WORKER_NAME: &str = "wasm_creole_service_worker";
+/// Files of the service worker itself, which are not cached with the app.
+const OWN_FILES: [&str; 3] = ["service-worker.js", "precache.js", WORKER_NAME];
+
+/// Files of a build of the app, set as `self.PRECACHE` by `precache.js`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Precache {
+    /// Changes with the content of any file.
+    pub version: String,
+    /// Absolute paths of the files.
+    pub files: Vec<String>,
+    /// Script and module of this service worker.
+    pub worker: (String, String),
+}
+
+impl Precache {
+    /// Manifest of the files of a build, given by their path relative to its root.
+    pub fn new(files: &[(String, Vec<u8>)]) -> Precache {
+        let mut h = DefaultHasher::new();
+        let mut paths = vec![];
+        let mut worker = (String::new(), String::new());
+        // the same files give the same version, whatever order they are listed in
+        let mut files: Vec<&(String, Vec<u8>)> = files.iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        for (path, content) in files {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            if name.starts_with(WORKER_NAME) && name.ends_with("_bg.wasm") {
+                worker.1 = format!("/{path}");
+            } else if name.starts_with(WORKER_NAME) && name.ends_with(".js") {
+                worker.0 = format!("/{path}");
+            }
+            if OWN_FILES.iter().any(|f| name.starts_with(f)) {
+                continue;
+            }
+            path.hash(&mut h);
+            content.hash(&mut h);
+            paths.push(format!("/{path}"));
+        }
+        Precache { version: format!("{:016x}", h.finish()), files: paths, worker }
+    }
+
+    pub fn cache_name(&self) -> String {
+        format!("{CACHE_PREFIX}{}", self.version)
+    }
+}
+
+fn global() -> ServiceWorkerGlobalScope {
+    js_sys::global().unchecked_into()
+}
+
+fn precache() -> Result<Precache, JsValue> {
+    let value = js_sys::Reflect::get(&global(), &"PRECACHE".into())?;
+    let json: String = js_sys::JSON::stringify(&value)?.into();
+    serde_json::from_str(&json).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+async fn open_cache() -> Result<Cache, JsValue> {
+    let name = precache()?.cache_name();
+    Ok(JsFuture::from(global().caches()?.open(&name)).await?.unchecked_into())
+}
+
+/// Caches every file of the build before the worker takes over.
+#[wasm_bindgen]
+pub fn on_install(_e: ExtendableEvent) -> js_sys::Promise {
+    future_to_promise(async {
+        let files = precache()?.files.into_iter().map(JsValue::from).collect::<js_sys::Array>();
+        JsFuture::from(open_cache().await?.add_all_with_str_sequence(&files)).await
+    })
+}
+
+/// Deletes the caches of other builds, and takes over the pages opened before.
+#[wasm_bindgen]
+pub fn on_activate(_e: ExtendableEvent) -> js_sys::Promise {
+    future_to_promise(async {
+        let current = precache()?.cache_name();
+        let caches = global().caches()?;
+        let names: js_sys::Array = JsFuture::from(caches.keys()).await?.unchecked_into();
+        for name in names.iter().filter_map(|n| n.as_string()) {
+            if name.starts_with(CACHE_PREFIX) && name != current {
+                JsFuture::from(caches.delete(&name)).await?;
+            }
+        }
+        JsFuture::from(global().clients().claim()).await
+    })
+}
+
+/// Serves files from the cache first. Navigations go to the network first,
+/// and to the app page when offline, so that any path of the wiki opens.
+#[wasm_bindgen]
+pub fn on_fetch(e: FetchEvent) -> js_sys::Promise {
+    let request: Request = e.request();
+    future_to_promise(async move {
+        if request.method() != "GET" {
+            return JsFuture::from(global().fetch_with_request(&request)).await;
+        }
+        let caches = global().caches()?;
+        if request.mode() == RequestMode::Navigate {
+            return match JsFuture::from(global().fetch_with_request(&request)).await {
+                Ok(response) => Ok(response),
+                Err(e) => {
+                    let cached = JsFuture::from(caches.match_with_str(FALLBACK)).await?;
+                    if cached.is_undefined() { Err(e) } else { Ok(cached) }
+                }
+            };
+        }
+        let cached = JsFuture::from(caches.match_with_request(&request)).await?;
+        if cached.dyn_ref::<Response>().is_some() {
+            return Ok(cached);
+        }
+        JsFuture::from(global().fetch_with_request(&request)).await
+    })
+}
+
+/// Takes over from the previous version when the app asks for it.
+#[wasm_bindgen]
+pub fn on_message(e: ExtendableMessageEvent) -> js_sys::Promise {
+    if e.data().as_string().as_deref() == Some(SKIP_WAITING) {
+        return global().skip_waiting().unwrap_or_else(|e| js_sys::Promise::reject(&e));
+    }
+    js_sys::Promise::resolve(&JsValue::UNDEFINED)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(index: &str) -> Precache {
+        Precache::new(&[
+            ("index.html".into(), index.into()),
+            ("app-1a2b_bg.wasm".into(), vec![0, 1]),
+            ("wasm_creole_service_worker-3c4d.js".into(), vec![]),
+            ("wasm_creole_service_worker-3c4d_bg.wasm".into(), vec![2]),
+            ("precache.js".into(), vec![]),
+        ])
+    }
+
+    #[test]
+    fn manifest() {
+        let p = build("a");
+        assert_eq!(p.files, vec!["/app-1a2b_bg.wasm", "/index.html"]);
+        assert_eq!(p.worker, (
+            "/wasm_creole_service_worker-3c4d.js".into(),
+            "/wasm_creole_service_worker-3c4d_bg.wasm".into(),
+        ));
+        assert_eq!(p.version, build("a").version);
+        assert_ne!(p.cache_name(), build("b").cache_name());
+    }
+}
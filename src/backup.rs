@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+use js_sys::{Array, Date};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Event, HtmlInputElement};
+
+use crate::history::Revision;
+use crate::record::PageRecord;
+use crate::storage::{self, PageWrite};
+use crate::{download, open_db_transaction, site, write_pages, HISTORY_STORE, STORE_NAME};
+
+/// Version 1 kept the bare text of pages, and version 2 their records.
+const ARCHIVE_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedRevision {
+    pub path: String,
+    pub time: f64,
+    pub text: String,
+}
+
+/// Everything the wiki keeps in IndexedDB, as written to a backup file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// Export time in milliseconds since the epoch.
+    pub exported: f64,
+    #[serde(deserialize_with = "records")]
+    pub pages: BTreeMap<String, PageRecord>,
+    #[serde(default)]
+    pub history: Vec<ArchivedRevision>,
+}
+
+/// Pages of an archive of any version.
+fn records<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<String, PageRecord>, D::Error> {
+    BTreeMap::<String, Value>::deserialize(d)?
+        .into_iter()
+        .map(|(path, v)| match PageRecord::from_value(v) {
+            Some(r) => Ok((path, r)),
+            None => Err(D::Error::custom(format!("/{path} is not a page"))),
+        })
+        .collect()
+}
+
+/// Reads every page and revision into an [`Archive`].
+pub async fn export(cx: Scope<'_>) -> storage::Result<Archive> {
+    let transaction = open_db_transaction(cx, &[STORE_NAME, HISTORY_STORE], false)?;
+    let pages = storage::entries(&transaction.object_store(STORE_NAME)?).await?;
+    let revisions = storage::entries(&transaction.object_store(HISTORY_STORE)?).await?;
+    Ok(Archive {
+        version: ARCHIVE_VERSION,
+        exported: Date::now(),
+        pages: pages.into_iter()
+          .filter_map(|(k, v)| Some((k.as_string()?, PageRecord::from_js(&v)?)))
+          .collect(),
+        history: revisions.into_iter()
+          .filter_map(|(k, v)| {
+              let k = Array::from(&k);
+              Some(ArchivedRevision { path: k.get(0).as_string()?, time: k.get(1).as_f64()?, text: v.as_string()? })
+          })
+          .collect(),
+    })
+}
+
+/// An archive read from a file, compared against the pages already stored.
+pub struct Import {
+    archive: Archive,
+    existing: HashMap<String, String>,
+}
+
+impl Import {
+    pub async fn new(cx: Scope<'_>, archive: Archive) -> storage::Result<Self> {
+        let store = open_db_transaction(cx, &[STORE_NAME], false)?.object_store(STORE_NAME)?;
+        let existing = storage::entries(&store).await?.into_iter()
+          .filter_map(|(k, v)| Some((k.as_string()?, PageRecord::from_js(&v)?.text)))
+          .collect();
+        Ok(Import { archive, existing })
+    }
+    /// Pages of the archive that do not exist yet.
+    fn added(&self) -> impl Iterator<Item = &String> {
+        self.archive.pages.keys().filter(|k| !self.existing.contains_key(*k))
+    }
+    /// Pages of the archive that exist with a different content.
+    fn conflicts(&self) -> impl Iterator<Item = &String> {
+        self.archive.pages.iter()
+          .filter(|(k, v)| self.existing.get(*k).is_some_and(|e| *e != v.text))
+          .map(|(k, _)| k)
+    }
+    /// Writes the archive, replacing conflicting pages only if `overwrite` is set.
+    /// Returns the number of pages written.
+    /// Pages keep the times of their archived record, and only their archived history:
+    /// importing them adds no revision, and pages kept as they were get none from the archive.
+    pub async fn apply(&self, cx: Scope<'_>, overwrite: bool) -> storage::Result<usize> {
+        let pages: Vec<PageWrite> = self.archive.pages.iter()
+          .filter(|(path, r)| match self.existing.get(*path) {
+              Some(e) => overwrite && *e != r.text,
+              None => true,
+          })
+          .map(|(path, r)| PageWrite {
+              record: Some(PageRecord::restored(r)),
+              revision: false,
+              ..PageWrite::saved(path, &r.text)
+          })
+          .collect();
+        let written: HashSet<&str> = pages.iter().map(|p| p.path.as_str()).collect();
+        let history = self.archive.history.iter()
+          .filter(|r| written.contains(r.path.as_str()))
+          .map(|r| (r.path.clone(), Revision { time: r.time, text: r.text.clone() }))
+          .collect();
+        let count = pages.len();
+        write_pages(cx, pages, history).await?;
+        Ok(count)
+    }
+}
+
+#[component]
+pub fn Backup<G: Html>(cx: Scope) -> View<G> {
+    let pending = create_signal(cx, None::<Rc<Import>>);
+    let status = create_signal(cx, String::new());
+
+    let on_export = move |_| spawn_local_scoped(cx, async move {
+        let archive = match export(cx).await {
+            Ok(archive) => archive,
+            Err(e) => return status.set(e.to_string()),
+        };
+        let name = format!("wiki-{}.json", String::from(Date::new_0().to_iso_string()).get(..10).unwrap_or_default());
+        let json = serde_json::to_string_pretty(&archive).expect("archive is serializable");
+        download(&name, "application/json", json.as_bytes());
+        status.set(format!("exported {} pages", archive.pages.len()));
+    });
+    let on_site = move |_| spawn_local_scoped(cx, async move {
+        match site::export(cx).await {
+            Ok(zip) => {
+                download("wiki-site.zip", "application/zip", &zip);
+                status.set(String::from("exported static site"));
+            }
+            Err(e) => status.set(e.to_string()),
+        }
+    });
+    let on_file = move |e: Event| {
+        let input: HtmlInputElement = e.target().expect("no input").unchecked_into();
+        if let Some(file) = input.files().and_then(|f| f.get(0)) {
+            spawn_local_scoped(cx, async move {
+                let text = JsFuture::from(file.text()).await.ok().and_then(|t| t.as_string()).unwrap_or_default();
+                match serde_json::from_str::<Archive>(&text) {
+                    Ok(archive) => match Import::new(cx, archive).await {
+                        Ok(import) => pending.set(Some(Rc::new(import))),
+                        Err(e) => status.set(e.to_string()),
+                    },
+                    Err(e) => status.set(format!("{} is not a wiki archive : {e}", file.name())),
+                }
+            });
+        }
+    };
+
+    view! { cx,
+      div(class="backup") {
+        h2(class="view-name") { "Backup" }
+        p {
+          button(on:click=on_export) { "Export" }
+          " every page and its history as a JSON file."
+        }
+        p {
+          button(on:click=on_site) { "Export as static site" }
+          " to publish every page as linked HTML files in a zip archive."
+        }
+        p {
+          "Import from a file : "
+          input(type="file", accept=".json,application/json", on:change=on_file)
+        }
+        p(class="status") { (status.get()) }
+        (match (*pending.get()).clone() {
+          Some(import) => {
+            let summary = format!("{} pages, {} new, {} conflicting with existing pages :",
+              import.archive.pages.len(), import.added().count(), import.conflicts().count());
+            let conflicts = View::new_fragment(import.conflicts().map(|k| {
+                let k = k.clone();
+                view! { cx, li { (k) } }
+            }).collect());
+            let import_merge = import.clone();
+            let import_overwrite = import.clone();
+            let on_merge = move |_| {
+                let import = import_merge.clone();
+                pending.set(None);
+                spawn_local_scoped(cx, async move {
+                    match import.apply(cx, false).await {
+                        Ok(n) => status.set(format!("imported {n} pages, existing pages kept")),
+                        Err(e) => status.set(e.to_string()),
+                    }
+                });
+            };
+            let on_overwrite = move |_| {
+                let import = import_overwrite.clone();
+                pending.set(None);
+                spawn_local_scoped(cx, async move {
+                    match import.apply(cx, true).await {
+                        Ok(n) => status.set(format!("imported {n} pages, existing pages overwritten")),
+                        Err(e) => status.set(e.to_string()),
+                    }
+                });
+            };
+            view! { cx,
+              div(class="import") {
+                p { (summary) }
+                ul { (conflicts) }
+                button(on:click=on_merge) { "Merge" }
+                button(on:click=on_overwrite) { "Overwrite" }
+                button(on:click=move |_| pending.set(None)) { "Cancel" }
+              }
+            }
+          }
+          None => view! { cx, },
+        })
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives() {
+        let old: Archive = serde_json::from_str(r#"{ "version": 1, "exported": 0, "pages": { "a": "= A" } }"#).unwrap();
+        assert_eq!(old.pages["a"], PageRecord::legacy("= A"));
+
+        let record = PageRecord::saved("b c", 1., None);
+        let archive = Archive { version: ARCHIVE_VERSION, exported: 2., pages: [(String::from("b"), record.clone())].into(), history: vec![] };
+        let read: Archive = serde_json::from_str(&serde_json::to_string(&archive).unwrap()).unwrap();
+        assert_eq!(read.pages["b"], record);
+        assert!(serde_json::from_str::<Archive>(r#"{ "version": 2, "exported": 0, "pages": { "a": 1 } }"#).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+//! Web worker parsing the blocks of large pages off the main thread.
+
+#[allow(dead_code)]
+#[path = "../tree.rs"]
+mod tree;
+
+use creole_nom::prelude::*;
+use wasm_bindgen::{closure::Closure, JsCast};
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
+
+use tree::Reply;
+
+fn reply(request: Option<String>) -> Reply {
+    let request = match request.map(|s| serde_json::from_str::<tree::Request>(&s)) {
+        Some(Ok(r)) => r,
+        Some(Err(e)) => return Reply::Failed { id: None, reason: e.to_string() },
+        None => return Reply::Failed { id: None, reason: String::from("request is not a string") },
+    };
+    let trees = request.blocks.iter().map(|b| tree::encode(b, &creoles(b))).collect();
+    Reply::Parsed { id: request.id, trees }
+}
+
+fn post(scope: &DedicatedWorkerGlobalScope, reply: &Reply) {
+    let json = serde_json::to_string(reply).unwrap_or_else(|e| {
+        let id = match reply {
+            Reply::Parsed { id, .. } => Some(*id),
+            _ => None,
+        };
+        let failed = Reply::Failed { id, reason: e.to_string() };
+        serde_json::to_string(&failed).unwrap_or_default()
+    });
+    scope.post_message(&json.into()).unwrap_or(());
+}
+
+fn main() {
+    console_error_panic_hook::set_once();
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    let post_to = scope.clone();
+    let on_message = Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+        post(&post_to, &reply(e.data().as_string()));
+    });
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    // messages sent while the module was loading had no listener, so the page waits for this one
+    post(&scope, &Reply::Ready);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replies_to_every_request() {
+        let request = serde_json::to_string(&tree::Request { id: 3, blocks: vec![String::from("**b**")] }).unwrap();
+        assert!(matches!(reply(Some(request)), Reply::Parsed { id: 3, trees } if trees.len() == 1));
+        assert!(matches!(reply(Some(String::from("{"))), Reply::Failed { id: None, .. }));
+        assert!(matches!(reply(None), Reply::Failed { id: None, .. }));
+    }
+}
@@ -0,0 +1,67 @@
+//! Splits Creole source into blocks that parse the same on their own, to re-parse only changed ones.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Lines of the source that are parsed together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Block<'s> {
+    pub text: &'s str,
+    /// Source line the block starts at.
+    pub line: usize,
+}
+
+/// Key of a block text in caches of parsed blocks.
+pub fn hash(text: &str) -> u64 {
+    let mut h = DefaultHasher::new();
+    text.hash(&mut h);
+    h.finish()
+}
+
+/// Blocks of `src`, which end at blank lines and before headings.
+/// Blank lines belong to no block, except inside `{{{` and `}}}` lines.
+pub fn split<'a>(src: &'a str) -> Vec<Block<'a>> {
+    let mut rst = vec![];
+    let mut start: Option<(usize, usize)> = None;
+    let mut nowiki = false;
+    let mut offset = 0;
+    let close = |rst: &mut Vec<Block<'a>>, start: &mut Option<(usize, usize)>, end: usize| {
+        if let Some((from, line)) = start.take() {
+            rst.push(Block { text: src[from..end].trim_end_matches(['\n', '\r']), line });
+        }
+    };
+    for (n, line) in src.split_inclusive('\n').enumerate() {
+        let t = line.trim_end_matches(['\n', '\r']);
+        if nowiki {
+            nowiki = t != "}}}";
+        } else if t.trim().is_empty() {
+            close(&mut rst, &mut start, offset);
+        } else {
+            if t.starts_with('=') {
+                close(&mut rst, &mut start, offset);
+            }
+            nowiki = t == "{{{";
+            start.get_or_insert((offset, n));
+        }
+        offset += line.len();
+    }
+    close(&mut rst, &mut start, offset);
+    rst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(src: &str) -> Vec<(&str, usize)> {
+        split(src).iter().map(|b| (b.text, b.line)).collect()
+    }
+
+    #[test]
+    fn blocks() {
+        assert_eq!(texts("a\nb\n\n\nc"), vec![("a\nb", 0), ("c", 4)]);
+        assert_eq!(texts("p\n= h\ntext\n== h2"), vec![("p", 0), ("= h\ntext", 1), ("== h2", 3)]);
+        assert_eq!(texts("{{{\nx\n\n= y\n}}}\n\nz\n"), vec![("{{{\nx\n\n= y\n}}}", 0), ("z", 6)]);
+        assert_eq!(texts("\n\n"), vec![]);
+    }
+}
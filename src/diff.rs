@@ -0,0 +1,75 @@
+/// A single line of a line-level diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diff<'a> {
+    Same(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// Line-level diff from `old` to `new` based on the longest common subsequence.
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Diff<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // common prefix and suffix do not need the quadratic table
+    let prefix = a.iter().zip(&b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (ma, mb) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    // lcs[i][j] : length of the LCS of ma[i..] and mb[j..]
+    let mut lcs = vec![vec![0usize; mb.len() + 1]; ma.len() + 1];
+    for i in (0..ma.len()).rev() {
+        for j in (0..mb.len()).rev() {
+            lcs[i][j] = if ma[i] == mb[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut rst: Vec<Diff> = a[..prefix].iter().map(|l| Diff::Same(l)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < ma.len() && j < mb.len() {
+        if ma[i] == mb[j] {
+            rst.push(Diff::Same(ma[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            rst.push(Diff::Removed(ma[i]));
+            i += 1;
+        } else {
+            rst.push(Diff::Added(mb[j]));
+            j += 1;
+        }
+    }
+    rst.extend(ma[i..].iter().map(|l| Diff::Removed(l)));
+    rst.extend(mb[j..].iter().map(|l| Diff::Added(l)));
+    rst.extend(a[a.len() - suffix..].iter().map(|l| Diff::Same(l)));
+    rst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Diff::*;
+
+    #[test]
+    fn lines() {
+        assert_eq!(diff_lines("", ""), []);
+        assert_eq!(diff_lines("a\nb", "a\nb"), [Same("a"), Same("b")]);
+        assert_eq!(diff_lines("", "a\nb"), [Added("a"), Added("b")]);
+        assert_eq!(diff_lines("a\nb", ""), [Removed("a"), Removed("b")]);
+        assert_eq!(diff_lines("a\nc", "a\nb\nc"), [Same("a"), Added("b"), Same("c")]);
+        assert_eq!(diff_lines("a\nb\nc", "a\nc"), [Same("a"), Removed("b"), Same("c")]);
+        assert_eq!(
+            diff_lines("a\nb\nx\nc\nd", "a\ny\nx\nz\nd"),
+            [Same("a"), Removed("b"), Added("y"), Same("x"), Removed("c"), Added("z"), Same("d")]
+        );
+    }
+}
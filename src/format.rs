@@ -0,0 +1,206 @@
+//! Formatting commands of the editor toolbar and keyboard shortcuts.
+
+/// A formatting command applied to the selected text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Bold,
+    Italic,
+    Link,
+    Heading,
+    Indent,
+    Outdent,
+    TableRow,
+}
+
+impl Action {
+    pub const TOOLBAR: [Action; 7] = [
+        Action::Bold,
+        Action::Italic,
+        Action::Link,
+        Action::Heading,
+        Action::Indent,
+        Action::Outdent,
+        Action::TableRow,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::Bold => "Bold",
+            Action::Italic => "Italic",
+            Action::Link => "Link",
+            Action::Heading => "Heading",
+            Action::Indent => "Indent",
+            Action::Outdent => "Outdent",
+            Action::TableRow => "Table row",
+        }
+    }
+
+    pub fn shortcut(self) -> &'static str {
+        match self {
+            Action::Bold => "Ctrl+B",
+            Action::Italic => "Ctrl+I",
+            Action::Link => "Ctrl+K",
+            Action::Heading => "Ctrl+Shift+H",
+            Action::Indent => "Tab",
+            Action::Outdent => "Shift+Tab",
+            Action::TableRow => "Ctrl+Enter",
+        }
+    }
+
+    /// The command bound to a key pressed with the given modifiers.
+    pub fn from_key(key: &str, ctrl: bool, shift: bool) -> Option<Action> {
+        match (key, ctrl, shift) {
+            ("b" | "B", true, false) => Some(Action::Bold),
+            ("i" | "I", true, false) => Some(Action::Italic),
+            ("k" | "K", true, false) => Some(Action::Link),
+            ("h" | "H", true, true) => Some(Action::Heading),
+            ("Tab", false, false) => Some(Action::Indent),
+            ("Tab", false, true) => Some(Action::Outdent),
+            ("Enter", true, false) => Some(Action::TableRow),
+            _ => None,
+        }
+    }
+
+    /// `src` changed by the command, with the new selection, for the bytes `start..end` selected.
+    /// `None` when the command does not apply, like indenting lines that are not list items.
+    pub fn apply(self, src: &str, start: usize, end: usize) -> Option<(String, usize, usize)> {
+        match self {
+            Action::Bold => Some(wrap(src, start, end, "**", "**")),
+            Action::Italic => Some(wrap(src, start, end, "//", "//")),
+            Action::Link => Some(wrap(src, start, end, "[[", "]]")),
+            Action::Heading => map_lines(src, start, end, |l| Some(cycle_heading(l))),
+            Action::Indent => map_lines(src, start, end, |l| list_marker(l).map(|_| format!("{}{l}", &l[..1]))),
+            Action::Outdent => map_lines(src, start, end, |l| list_marker(l).filter(|m| *m > 1).map(|_| l[1..].to_owned())),
+            Action::TableRow => Some(table_row(src, end)),
+        }
+    }
+}
+
+/// Surrounds the selection with `open` and `close`, or removes them if they are already there.
+fn wrap(src: &str, start: usize, end: usize, open: &str, close: &str) -> (String, usize, usize) {
+    let sel = &src[start..end];
+    if src[..start].ends_with(open) && src[end..].starts_with(close) {
+        let text = format!("{}{sel}{}", &src[..start - open.len()], &src[end + close.len()..]);
+        return (text, start - open.len(), end - open.len());
+    }
+    let text = format!("{}{open}{sel}{close}{}", &src[..start], &src[end..]);
+    (text, start + open.len(), end + open.len())
+}
+
+/// Length of the `*` or `#` run starting a list item line.
+fn list_marker(line: &str) -> Option<usize> {
+    let first = line.chars().next().filter(|c| *c == '*' || *c == '#')?;
+    let len = line.len() - line.trim_start_matches(first).len();
+    line[len..].starts_with(' ').then_some(len)
+}
+
+/// `line` one heading level deeper, back to a paragraph after the sixth level.
+fn cycle_heading(line: &str) -> String {
+    let level = line.len() - line.trim_start_matches('=').len();
+    let text = line.trim_start_matches('=').trim_start();
+    match level {
+        0 => format!("= {text}"),
+        1..=5 => format!("{} {text}", "=".repeat(level + 1)),
+        _ => text.to_owned(),
+    }
+}
+
+/// Replaces every line touched by `start..end` by `f` of it, when `f` applies to any of them.
+/// The new selection covers the changed lines.
+fn map_lines(src: &str, start: usize, end: usize, f: impl Fn(&str) -> Option<String>) -> Option<(String, usize, usize)> {
+    let from = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    let to = src[end..].find('\n').map_or(src.len(), |i| end + i);
+    let mut changed = false;
+    let lines: Vec<String> = src[from..to]
+        .split('\n')
+        .map(|l| match f(l) {
+            Some(n) => {
+                changed = true;
+                n
+            }
+            None => l.to_owned(),
+        })
+        .collect();
+    if !changed {
+        return None;
+    }
+    let lines = lines.join("\n");
+    let text = format!("{}{lines}{}", &src[..from], &src[to..]);
+    if start == end {
+        // keeps the caret at the end of a single line
+        let caret = from + lines.len();
+        return Some((text, caret, caret));
+    }
+    Some((text, from, from + lines.len()))
+}
+
+/// Adds an empty row after the table row holding `at`, with as many cells as it has.
+/// Outside of a table, starts a table of two cells.
+fn table_row(src: &str, at: usize) -> (String, usize, usize) {
+    let from = src[..at].rfind('\n').map_or(0, |i| i + 1);
+    let to = src[at..].find('\n').map_or(src.len(), |i| at + i);
+    let line = src[from..to].trim_end();
+    let row = if line.starts_with('|') {
+        "|  ".repeat(line.trim_end_matches('|').matches('|').count().max(1)) + "|"
+    } else {
+        "|  |  |".to_owned()
+    };
+    let prefix = if line.is_empty() { "" } else { "\n" };
+    let text = format!("{}{prefix}{row}{}", &src[..to], &src[to..]);
+    // the caret goes into the first new cell
+    let caret = to + prefix.len() + 2;
+    (text, caret, caret)
+}
+
+/// Byte offset in `src` of the UTF-16 `offset` of DOM selections.
+pub fn to_byte(src: &str, offset: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in src.char_indices() {
+        if units >= offset {
+            return i;
+        }
+        units += c.len_utf16() as u32;
+    }
+    src.len()
+}
+
+/// UTF-16 offset of the byte `offset` in `src`.
+pub fn to_utf16(src: &str, offset: usize) -> u32 {
+    src[..offset].chars().map(|c| c.len_utf16() as u32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(Action::Bold.apply("a b c", 2, 3), Some(("a **b** c".into(), 4, 5)));
+        assert_eq!(Action::Bold.apply("a **b** c", 4, 5), Some(("a b c".into(), 2, 3)));
+        assert_eq!(Action::Link.apply("", 0, 0), Some(("[[]]".into(), 2, 2)));
+    }
+
+    #[test]
+    fn lines() {
+        assert_eq!(Action::Heading.apply("x\nab", 3, 3), Some(("x\n= ab".into(), 6, 6)));
+        assert_eq!(Action::Heading.apply("== ab", 0, 0), Some(("=== ab".into(), 6, 6)));
+        assert_eq!(Action::Heading.apply("====== ab", 0, 0), Some(("ab".into(), 2, 2)));
+        assert_eq!(Action::Indent.apply("* a\nb\n# c", 0, 9), Some(("** a\nb\n## c".into(), 0, 11)));
+        assert_eq!(Action::Indent.apply("a", 0, 0), None);
+        assert_eq!(Action::Outdent.apply("** a", 1, 1), Some(("* a".into(), 3, 3)));
+        assert_eq!(Action::Outdent.apply("* a", 1, 1), None);
+    }
+
+    #[test]
+    fn table_rows() {
+        assert_eq!(Action::TableRow.apply("|a|b|\nx", 2, 2), Some(("|a|b|\n|  |  |\nx".into(), 8, 8)));
+        assert_eq!(Action::TableRow.apply("", 0, 0), Some(("|  |  |".into(), 2, 2)));
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(to_byte("é한x", 2), 5);
+        assert_eq!(to_utf16("é한x", 5), 2);
+        assert_eq!(to_byte("ab", 9), 2);
+    }
+}
@@ -0,0 +1,170 @@
+//! Tokenizer of Creole source for the highlighted editor overlay.
+
+use crate::html::escape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    Heading,
+    Bold,
+    Italic,
+    Link,
+    Image,
+    Nowiki,
+    ListMarker,
+    TablePipe,
+    HorizontalLine,
+    Linebreak,
+    /// Markup that is opened but never closed.
+    Error,
+}
+
+impl Kind {
+    fn class(self) -> &'static str {
+        match self {
+            Kind::Text => "",
+            Kind::Heading => "hl-heading",
+            Kind::Bold => "hl-bold",
+            Kind::Italic => "hl-italic",
+            Kind::Link => "hl-link",
+            Kind::Image => "hl-image",
+            Kind::Nowiki => "hl-nowiki",
+            Kind::ListMarker => "hl-list",
+            Kind::TablePipe => "hl-table",
+            Kind::HorizontalLine => "hl-hr",
+            Kind::Linebreak => "hl-br",
+            Kind::Error => "hl-error",
+        }
+    }
+}
+
+/// Closed markup starting at the beginning of `s` as its kind and length.
+fn inline_token(s: &str) -> Option<(Kind, usize)> {
+    let closed = |open: &str, close: &str, kind: Kind| {
+        s.strip_prefix(open).map(|rest| match rest.find(close) {
+            Some(e) => (kind, open.len() + e + close.len()),
+            None => (Kind::Error, s.len()),
+        })
+    };
+    closed("{{{", "}}}", Kind::Nowiki)
+        .or_else(|| closed("[[", "]]", Kind::Link))
+        .or_else(|| closed("{{", "}}", Kind::Image))
+        .or_else(|| s.starts_with("**").then_some((Kind::Bold, 2)))
+        .or_else(|| s.starts_with("//").then_some((Kind::Italic, 2)))
+        .or_else(|| s.starts_with("\\\\").then_some((Kind::Linebreak, 2)))
+}
+
+fn inline<'a>(line: &'a str, table: bool, rst: &mut Vec<(Kind, &'a str)>) {
+    let mut text = 0;
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let token = if table && rest.starts_with("|=") {
+            Some((Kind::TablePipe, 2))
+        } else if table && rest.starts_with('|') {
+            Some((Kind::TablePipe, 1))
+        } else if rest.starts_with("//") && line[..i].ends_with(':') {
+            // the scheme of an url, not italic
+            None
+        } else {
+            inline_token(rest)
+        };
+        match token {
+            Some((kind, len)) => {
+                if text < i {
+                    rst.push((Kind::Text, &line[text..i]));
+                }
+                rst.push((kind, &line[i..i + len]));
+                i += len;
+                text = i;
+            }
+            None => i += rest.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if text < line.len() {
+        rst.push((Kind::Text, &line[text..]));
+    }
+}
+
+/// Splits `src` into tokens covering all of it.
+pub fn tokens(src: &str) -> Vec<(Kind, &str)> {
+    let mut rst = vec![];
+    let mut nowiki = false;
+    for line in src.split_inclusive('\n') {
+        let t = line.trim_end_matches(['\n', '\r']);
+        if nowiki {
+            rst.push((Kind::Nowiki, line));
+            nowiki = t != "}}}";
+        } else if t == "{{{" {
+            // unclosed blocks are errors until the closing line shows up
+            nowiki = true;
+            let closed = src[line.as_ptr() as usize - src.as_ptr() as usize..].lines().skip(1).any(|l| l == "}}}");
+            rst.push((if closed { Kind::Nowiki } else { Kind::Error }, line));
+        } else if t.starts_with('=') {
+            rst.push((Kind::Heading, line));
+        } else if t == "----" {
+            rst.push((Kind::HorizontalLine, line));
+        } else if let Some(marker) = t.find(|c| c != '*' && c != '#').filter(|m| *m > 0 && t[*m..].starts_with(' ')) {
+            rst.push((Kind::ListMarker, &line[..marker]));
+            inline(&line[marker..], false, &mut rst);
+        } else {
+            inline(line, t.starts_with('|'), &mut rst);
+        }
+    }
+    rst
+}
+
+/// `src` as HTML with every token wrapped in a span of its kind,
+/// and every line in a `line` span so that its position can be measured.
+pub fn highlight(src: &str) -> String {
+    let mut rst = String::with_capacity(src.len() * 2);
+    rst.push_str("<span class=\"line\">");
+    for (kind, t) in tokens(src) {
+        match kind {
+            Kind::Text => rst.push_str(&escape(t)),
+            k => rst.push_str(&format!("<span class=\"{}\">{}</span>", k.class(), escape(t))),
+        }
+        if t.ends_with('\n') {
+            rst.push_str("</span><span class=\"line\">");
+        }
+    }
+    // keeps the height of a trailing empty line like the textarea does
+    if src.ends_with('\n') || src.is_empty() {
+        rst.push(' ');
+    }
+    rst.push_str("</span>");
+    rst
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Kind::*;
+
+    #[test]
+    fn tokenize() {
+        assert_eq!(tokens("= h\n* **a** [[b|c]]"), vec![
+            (Heading, "= h\n"),
+            (ListMarker, "*"),
+            (Text, " "),
+            (Bold, "**"),
+            (Text, "a"),
+            (Bold, "**"),
+            (Text, " "),
+            (Link, "[[b|c]]"),
+        ]);
+        assert_eq!(tokens("|=a|b {{x.png"), vec![
+            (TablePipe, "|="),
+            (Text, "a"),
+            (TablePipe, "|"),
+            (Text, "b "),
+            (Error, "{{x.png"),
+        ]);
+        assert_eq!(tokens("{{{\n//x//\n}}}\nhttp://y"), vec![
+            (Nowiki, "{{{\n"),
+            (Nowiki, "//x//\n"),
+            (Nowiki, "}}}\n"),
+            (Text, "http://y"),
+        ]);
+    }
+}
@@ -0,0 +1,123 @@
+use js_sys::Array;
+use sycamore::prelude::*;
+use wasm_bindgen::prelude::*;
+use web_sys::{IdbKeyRange, IdbObjectStore};
+
+use crate::diff::{diff_lines, Diff};
+use crate::storage::{self, StorageFailure};
+use crate::{encode_path, format_time, open_db_transaction, save_page, HISTORY_STORE};
+
+/// A saved state of a page. An empty text means the page was deleted.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub time: f64,
+    pub text: String,
+}
+
+/// Revisions are keyed by `[path, time]` so that one page's history is a single key range.
+fn revision_key(path: &str, time: f64) -> JsValue {
+    Array::of2(&path.into(), &time.into()).into()
+}
+
+pub fn put(store: &IdbObjectStore, path: &str, time: f64, text: &str) -> storage::Result<()> {
+    store.put_with_key(&text.into(), &revision_key(path, time))?;
+    Ok(())
+}
+
+pub fn delete(store: &IdbObjectStore, path: &str, time: f64) -> storage::Result<()> {
+    store.delete(&revision_key(path, time))?;
+    Ok(())
+}
+
+/// Milliseconds after a save during which typing more goes into the same revision.
+pub const MERGE_WINDOW: f64 = 2. * 60. * 1000.;
+
+/// Time of the revision that a save at `now` replaces, for a page last saved at `updated`.
+pub fn merged(updated: Option<f64>, now: f64) -> Option<f64> {
+    updated.filter(|u| *u < now && now - u < MERGE_WINDOW)
+}
+
+/// Every revision of `path`, newest first.
+pub async fn revisions(cx: Scope<'_>, path: &str) -> storage::Result<Vec<Revision>> {
+    let store = open_db_transaction(cx, &[HISTORY_STORE], false)?.object_store(HISTORY_STORE)?;
+    let range = IdbKeyRange::bound(&revision_key(path, 0.), &revision_key(path, f64::INFINITY))?;
+    let keys = store.get_all_keys_with_key(&range)?;
+    let values = store.get_all_with_key(&range)?;
+    let keys: Array = storage::request(&keys).await?.unchecked_into();
+    let values: Array = storage::request(&values).await?.unchecked_into();
+    Ok(keys.iter()
+        .zip(values.iter())
+        .map(|(k, v)| Revision {
+            time: Array::from(&k).get(1).as_f64().unwrap_or(0.),
+            text: v.as_string().unwrap_or_default(),
+        })
+        .rev()
+        .collect())
+}
+
+#[derive(Prop)]
+pub struct HistoryProps {
+    path: String,
+}
+
+#[component]
+pub async fn History<G: Html>(cx: Scope<'_>, props: HistoryProps) -> View<G> {
+    let path = create_ref(cx, props.path);
+    let revs = match revisions(cx, path).await {
+        Ok(revs) => create_ref(cx, revs),
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    };
+    if revs.is_empty() {
+        return view! { cx, p { (format!("/{path} has no revisions yet.")) } };
+    }
+
+    let from = create_signal(cx, revs.len().min(2) - 1);
+    let to = create_signal(cx, 0);
+    let rows = View::new_fragment(revs.iter().enumerate().map(|(i, r)| {
+        let time = format_time(r.time);
+        let size = if r.text.is_empty() { String::from("deleted") } else { format!("{} bytes", r.text.len()) };
+        let on_restore = move |_| {
+            save_page(cx, path, &revs[i].text);
+            sycamore_router::navigate(&format!("/w/{}", encode_path(path)));
+        };
+        view! { cx,
+          li {
+            button(class=if *from.get() == i { "selected" } else { "" }, on:click=move |_| from.set(i)) { "from" }
+            button(class=if *to.get() == i { "selected" } else { "" }, on:click=move |_| to.set(i)) { "to" }
+            span { (format!(" {time} ({size}) ")) }
+            button(on:click=on_restore) { "Restore" }
+          }
+        }
+    }).collect());
+    let diff = create_memo(cx, || diff_lines(&revs[*from.get()].text, &revs[*to.get()].text));
+
+    view! { cx,
+      div(class="history") {
+        h2(class="view-name") { (format!("History of /{path}")) }
+        ul { (rows) }
+        pre(class="diff") {
+          Indexed {
+            iterable: diff,
+            view: |cx, d: Diff| match d {
+              Diff::Same(l) => view! { cx, div { (format!("  {l}")) } },
+              Diff::Added(l) => view! { cx, div(class="diff-add") { (format!("+ {l}")) } },
+              Diff::Removed(l) => view! { cx, div(class="diff-del") { (format!("- {l}")) } },
+            }
+          }
+        }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merged, MERGE_WINDOW};
+
+    #[test]
+    fn merges_recent_saves() {
+        assert_eq!(merged(Some(1000.), 2000.), Some(1000.));
+        assert_eq!(merged(Some(1000.), 1000. + MERGE_WINDOW), None);
+        assert_eq!(merged(Some(2000.), 2000.), None);
+        assert_eq!(merged(None, 2000.), None);
+    }
+}
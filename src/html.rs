@@ -0,0 +1,244 @@
+//! Renders parsed Creole into an HTML string, without any DOM.
+
+use std::fmt::Write;
+
+use creole_nom::prelude::*;
+
+use crate::links::target_page;
+use crate::policy::{LinkPolicy, Target};
+use crate::tags::tag_name;
+use crate::toc::{entries, heading_text, slug, split};
+
+/// Escapes `s` for use in HTML text and double quoted attributes.
+pub fn escape(s: &str) -> String {
+    let mut rst = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => rst.push_str("&amp;"),
+            '<' => rst.push_str("&lt;"),
+            '>' => rst.push_str("&gt;"),
+            '"' => rst.push_str("&quot;"),
+            '\'' => rst.push_str("&#39;"),
+            c => rst.push(c),
+        }
+    }
+    rst
+}
+
+/// How links are written, with internal link targets mapped by `link` and tags by `tag`.
+pub struct Links<'r> {
+    pub policy: &'r LinkPolicy,
+    pub link: &'r dyn Fn(&str) -> String,
+    pub tag: &'r dyn Fn(&str) -> String,
+}
+
+/// Renders `tree` the way `CreoleItem` does.
+pub fn to_html(tree: &[ICreole], links: &Links) -> String {
+    let mut rst = String::new();
+    // headings sharing their text get ids of their own, numbered in page order
+    let mut ids = entries(tree).into_iter().map(|e| e.slug);
+    for i in tree {
+        match i {
+            ICreole::Heading(l, t) => write_heading(&mut rst, *l, t, &ids.next().unwrap_or_default(), links),
+            i => write_item(&mut rst, i, links),
+        }
+    }
+    rst
+}
+
+fn write_heading(out: &mut String, level: u8, children: &[ICreole], id: &str, links: &Links) {
+    let _ = write!(out, "<h{level} id=\"{}\">", escape(id));
+    for c in children {
+        write_item(out, c, links);
+    }
+    let _ = write!(out, "</h{level}>");
+}
+
+fn write_tag(out: &mut String, tag: &str, children: &[ICreole], links: &Links) {
+    let _ = write!(out, "<{tag}>");
+    for c in children {
+        write_item(out, c, links);
+    }
+    let _ = write!(out, "</{tag}>");
+}
+
+fn write_blocked(out: &mut String, what: &str, target: &str, text: &str) {
+    let _ = write!(out, "<span class=\"blocked-link\" title=\"Blocked {what} {}\">{}</span>", escape(target), escape(text));
+}
+
+fn write_item(out: &mut String, i: &ICreole, links: &Links) {
+    match i {
+        ICreole::Heading(l, t) => write_heading(out, *l, t, &slug(&heading_text(t)), links),
+        ICreole::Bold(children) => write_tag(out, "b", children, links),
+        ICreole::Italic(children) => write_tag(out, "i", children, links),
+        ICreole::Text(t) => out.push_str(&escape(t)),
+        ICreole::DontFormat(t) => {
+            let _ = write!(out, "<pre>{}</pre>", escape(t));
+        }
+        ICreole::Link(href, t) => match links.policy.target(href) {
+            Target::Page(href) => {
+                // tags list the pages carrying them, like `CreoleItem` links them
+                let href = match (tag_name(href), target_page(href), split(href).1) {
+                    (Some(tag), _, _) => (links.tag)(tag),
+                    (None, Some(page), Some(section)) => format!("{}#{}", (links.link)(page), slug(section)),
+                    (None, Some(page), None) => (links.link)(page),
+                    (None, None, section) => format!("#{}", slug(section.unwrap_or_default())),
+                };
+                let _ = write!(out, "<a href=\"{}\">{}</a>", escape(&href), escape(t));
+            }
+            Target::External(href) => {
+                let _ = write!(out, "<a href=\"{}\" target=\"_blank\">{}</a>", escape(href), escape(t));
+            }
+            Target::App(href) | Target::Script(href) => {
+                let _ = write!(out, "<a href=\"{}\">{}</a>", escape(href), escape(t));
+            }
+            Target::Blocked(href) => write_blocked(out, "link to", href, t),
+        },
+        ICreole::Line(l) => write_tag(out, "p", l, links),
+        ICreole::Image(src, t) => {
+            if let Target::Blocked(src) = links.policy.image(src) {
+                write_blocked(out, "image from", src, if t.is_empty() { src } else { t });
+            } else if t.is_empty() {
+                let _ = write!(out, "<img src=\"{}\">", escape(src));
+            } else {
+                let _ = write!(
+                    out,
+                    "<figure><img src=\"{}\" alt=\"{}\"><figcaption>{}</figcaption></figure>",
+                    escape(src),
+                    escape(t),
+                    escape(t)
+                );
+            }
+        }
+        ICreole::Silentbreak => out.push(' '),
+        ICreole::ForceLinebreak => out.push_str("<br>"),
+        ICreole::HorizontalLine => out.push_str("<hr>"),
+        ICreole::Table(children) => {
+            let (head, body): (Vec<&ICreole>, Vec<&ICreole>) =
+                children.iter().partition(|c| matches!(c, ICreole::TableHeaderRow(_)));
+            out.push_str("<table><thead>");
+            for c in head {
+                write_item(out, c, links);
+            }
+            out.push_str("</thead><tbody>");
+            for c in body {
+                write_item(out, c, links);
+            }
+            out.push_str("</tbody></table>");
+        }
+        ICreole::TableHeaderRow(children) | ICreole::TableRow(children) => {
+            write_tag(out, "tr", children, links)
+        }
+        ICreole::TableHeaderCell(children) | ICreole::TableCell(children) => {
+            write_tag(out, "td", children, links)
+        }
+        ICreole::BulletList(children) => write_tag(out, "ul", children, links),
+        ICreole::NumberedList(children) => write_tag(out, "ol", children, links),
+        ICreole::ListItem(children) => write_tag(out, "li", children, links),
+    }
+}
+
+/// File of a page in a static export, different for every page. The home page becomes `index.html`.
+/// Segments that can not be file names, and a page named `index`, start with a `%`
+/// which is escaped in any other segment.
+pub fn page_file(path: &str) -> String {
+    match path {
+        "" => return String::from("index.html"),
+        "index" => return String::from("%index.html"),
+        _ => {}
+    }
+    let segments: Vec<String> = path.split('/')
+        .map(|s| match s {
+            "" | "." | ".." => format!("%{s}"),
+            s => s.replace('%', "%25"),
+        })
+        .collect();
+    format!("{}.html", segments.join("/"))
+}
+
+/// File listing the pages carrying `tag` in a static export.
+/// Pages can not have it, as their segments only start with a `%` when they are `%`, `%.`, `%..` or `%index`.
+pub fn tag_file(tag: &str) -> String {
+    format!("%tags/{}", page_file(tag))
+}
+
+/// Relative path from `file` of the export to its root.
+pub fn root_of(file: &str) -> String {
+    "../".repeat(file.matches('/').count())
+}
+
+/// Relative path from the exported file of page `from` to the root of the export.
+pub fn root(from: &str) -> String {
+    root_of(&page_file(from))
+}
+
+/// Link to `file` of the export, from a file `root` away from the root of the export.
+pub fn file_href(root: &str, file: &str) -> String {
+    let file = file
+        .split('/')
+        .map(|s| urlencoding::encode(s).into_owned())
+        .collect::<Vec<String>>()
+        .join("/");
+    format!("{root}{file}")
+}
+
+/// Link from the exported file of page `from` to the one of page `to`.
+pub fn relative_href(from: &str, to: &str) -> String {
+    file_href(&root(from), &page_file(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(src: &str) -> String {
+        let tag = |tag: &str| file_href("", &tag_file(tag));
+        to_html(&creoles(src), &Links { policy: &LinkPolicy::default(), link: &|href| relative_href("", href), tag: &tag })
+    }
+
+    #[test]
+    fn text_styles() {
+        assert_eq!(render("//a// **b**"), "<p><i>a</i> <b>b</b></p>");
+        assert_eq!(render("= t **u**"), "<h1 id=\"t-u\">t <b>u</b></h1>");
+        assert_eq!(render("= a\n= a"), "<h1 id=\"a\">a</h1><h1 id=\"a-2\">a</h1>");
+        assert_eq!(render("a <b> & \"c\""), "<p>a &lt;b&gt; &amp; &quot;c&quot;</p>");
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            render("[[https://webassembly.org|WASM]]"),
+            "<p><a href=\"https://webassembly.org\" target=\"_blank\">WASM</a></p>"
+        );
+        assert_eq!(render("[[a b/c|x]] [[ a ]]"), "<p><a href=\"a%20b/c.html\">x</a> <a href=\"a.html\"> a </a></p>");
+        assert_eq!(render("[[a#B c|x]] [[#d]]"), "<p><a href=\"a.html#b-c\">x</a> <a href=\"#d\">#d</a></p>");
+        assert_eq!(render("[[tag/a b|x]]"), "<p><a href=\"%25tags/a%20b.html\">x</a></p>");
+        assert_eq!(
+            render("[[javascript:alert(1)|x]]"),
+            "<p><span class=\"blocked-link\" title=\"Blocked link to javascript:alert(1)\">x</span></p>"
+        );
+        assert_eq!(relative_href("a/b", "c"), "../c.html");
+        assert_eq!(relative_href("a/b/c", ""), "../../index.html");
+        assert_eq!(relative_href("", "../x"), "%25../x.html");
+        assert_eq!(file_href(&root_of(&tag_file("a/b")), &page_file("c")), "../../c.html");
+    }
+
+    #[test]
+    fn files() {
+        let paths = ["", "index", "%index", "a", "a//b", "a/b", "a/./b", "../x", "%../x", "100%", "a/index"];
+        let files: Vec<String> = paths.iter().map(|p| page_file(p)).collect();
+        assert_eq!(files, [
+            "index.html", "%index.html", "%25index.html", "a.html", "a/%/b.html", "a/b.html", "a/%./b.html",
+            "%../x.html", "%25../x.html", "100%25.html", "a/index.html",
+        ]);
+    }
+
+    #[test]
+    fn tables_and_lists() {
+        assert_eq!(
+            render("|=h|\n|c|"),
+            "<table><thead><tr><td>h</td></tr></thead><tbody><tr><td>c</td></tr></tbody></table>"
+        );
+        assert_eq!(render("* a\n* b"), "<ul><li>a</li><li>b</li></ul>");
+    }
+}
@@ -0,0 +1,111 @@
+use creole_nom::prelude::*;
+use js_sys::Array;
+use serde_json::{json, Value};
+use sycamore::prelude::*;
+use wasm_bindgen::prelude::*;
+use web_sys::{Event, IdbObjectStore};
+
+use crate::migrations::Schema;
+use crate::policy::is_page;
+use crate::storage::{self, StorageFailure};
+use crate::toc;
+use crate::{creole_children, encode_path, open_db_transaction, LINKS_STORE};
+
+const TARGET_INDEX: &str = "target";
+
+/// Page a link to `href` leads to, however it is spaced and without the section it names.
+/// `None` for links to a section of the page they are on.
+pub fn target_page(href: &str) -> Option<&str> {
+    Some(toc::split(href).0).filter(|p| !p.is_empty())
+}
+
+/// Internal pages linked from `tree`, sorted and without duplicates.
+pub fn targets(tree: &[ICreole]) -> Vec<String> {
+    fn walk(tree: &[ICreole], rst: &mut Vec<String>) {
+        for i in tree {
+            match i {
+                ICreole::Link(href, _) if is_page(href) => rst.extend(target_page(href).map(str::to_owned)),
+                _ => walk(creole_children(i), rst),
+            }
+        }
+    }
+    let mut rst = vec![];
+    walk(tree, &mut rst);
+    rst.sort();
+    rst.dedup();
+    rst
+}
+
+/// Creates the `links` store. Each record lists the targets of one page as
+/// `{ targets: [...] }` and the multi-entry `target` index inverts it.
+pub fn create_store(db: &mut dyn Schema) -> storage::Result<()> {
+    db.create_store(LINKS_STORE)?;
+    db.create_index(LINKS_STORE, TARGET_INDEX, "targets", true)
+}
+
+/// Record of the outgoing links in `text`, or `None` for a deleted page.
+pub fn record(text: &str) -> Option<Value> {
+    (!text.is_empty()).then(|| json!({ "targets": targets(&creoles(text)) }))
+}
+
+/// Replaces the outgoing links recorded for `path` by the ones in `text`.
+pub fn update(store: &IdbObjectStore, path: &str, text: &str) -> storage::Result<()> {
+    match record(text) {
+        Some(r) => store.put_with_key(&storage::to_js(&r), &path.into())?,
+        None => store.delete(&path.into())?,
+    };
+    Ok(())
+}
+
+/// Pages linking to `path`.
+pub async fn backlinks(cx: Scope<'_>, path: &str) -> storage::Result<Vec<String>> {
+    let store = open_db_transaction(cx, &[LINKS_STORE], false)?.object_store(LINKS_STORE)?;
+    let r = store.index(TARGET_INDEX)?.get_all_keys_with_key(&path.into())?;
+    let keys: Array = storage::request(&r).await?.unchecked_into();
+    Ok(keys.iter().filter_map(|k| k.as_string()).collect())
+}
+
+#[derive(Prop)]
+pub struct BacklinksProps {
+    path: String,
+}
+
+#[component]
+pub async fn Backlinks<G: Html>(cx: Scope<'_>, props: BacklinksProps) -> View<G> {
+    let pages = match backlinks(cx, &props.path).await {
+        Ok(pages) => pages,
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    };
+    if pages.is_empty() {
+        return view! { cx, };
+    }
+    let items = View::new_fragment(pages.into_iter().map(|p| {
+        let path = format!("/w/{}", encode_path(&p));
+        let href = path.clone();
+        let on_click = move |e: Event| {
+            e.prevent_default();
+            sycamore_router::navigate(&path);
+        };
+        let name = if p.is_empty() { String::from("(home)") } else { p };
+        view! { cx, li { a(href=href, on:click=on_click) { (name) } } }
+    }).collect());
+    view! { cx,
+      div(class="backlinks") {
+        h3 { "Linked from" }
+        ul { (items) }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized() {
+        let tree = creoles("[[ a ]] [[a|x]] [[b#Intro]] [[#c]] [[https://d]]");
+        assert_eq!(targets(&tree), ["a", "b"]);
+        assert_eq!(target_page(" a / b #c"), Some("a / b"));
+        assert_eq!(target_page("#c"), None);
+    }
+}
@@ -1,6 +1,9 @@
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
 use log::debug;
 
 use gloo_timers::future::TimeoutFuture;
@@ -8,31 +11,117 @@ use js_sys::{Date, };
 use sycamore::{/* builder::prelude::*,  */futures::spawn_local_scoped, suspense::Suspense, prelude::*};
 use sycamore_router::{HistoryIntegration, Router};
 use wasm_bindgen::{JsCast, closure::Closure, prelude::*};
-use wasm_bindgen_futures::JsFuture;
 
-use web_sys::{Event, /* HtmlInputElement,  */ HtmlTextAreaElement, InputEvent, IdbDatabase, IdbOpenDbRequest, IdbObjectStore};
+use web_sys::{Event, /* HtmlInputElement,  */ HtmlTextAreaElement, InputEvent, KeyboardEvent, IdbDatabase, IdbTransaction};
 
 use creole_nom::prelude::*;
 
+mod backup;
+mod blocks;
+mod diff;
+mod format;
+mod highlight;
+mod history;
+mod html;
+mod links;
+mod markdown;
+mod migrations;
+mod pages;
+mod parser;
+mod policy;
+mod record;
+mod rename;
 mod route;
+mod scroll;
+mod search;
+mod settings;
+mod site;
+mod tags;
+mod toc;
+mod storage;
+#[allow(dead_code)] // encoding is only used by the parser worker
+mod tree;
+mod update;
+mod zip;
+use backup::Backup;
+use format::Action;
+use history::History;
+use links::Backlinks;
+use markdown::MarkdownActions;
+use pages::PageList;
+use policy::{LinkPolicy, Target};
+use record::LastEdited;
+use rename::Rename;
+use search::Search;
+use storage::{IdbStore, PageStore, PageWrite, SaveFailure, StorageFailure};
+use tags::{TagCloud, Tagged};
+use toc::Toc;
+use settings::Settings;
+use update::UpdateBanner;
 use route::AppRoutes;
+use scroll::ScrollSync;
 
-use urlencoding::decode;
+use urlencoding::{decode, encode};
 
-// TODO : light/dark theme
 #[derive(Debug, Default, Clone)]
 pub struct AppState {
     pub theme: RcSignal<Theme>,
+    /// Whether the editor holds text that is not saved yet.
+    pub unsaved: RcSignal<bool>,
+    /// Paths of every page, to tell links to missing pages apart.
+    /// Not tracked by views, which show it as it was when rendered.
+    pub pages: RcSignal<HashSet<String>>,
+    /// Schemes that links of this wiki may use.
+    pub link_policy: RcSignal<LinkPolicy>,
+    /// A new version of the service worker, installed and waiting to take over.
+    pub waiting: RcSignal<Option<web_sys::ServiceWorker>>,
+    /// Path of the last page that could not be saved, with why, until the user dismisses it.
+    pub save_failure: RcSignal<Option<(String, storage::StorageError)>>,
+}
+
+impl AppState {
+    /// Keeps `pages` up to date after `text` is written at `path`, deleting the page when empty.
+    fn written(&self, path: &str, text: &str) {
+        if text.is_empty() {
+            self.pages.modify().remove(path);
+        } else if !self.pages.get_untracked().contains(path) {
+            self.pages.modify().insert(path.to_owned());
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum Theme {
+    #[default]
     Light,
     Dark,
 }
-impl Default for Theme {
-    fn default() -> Self {
-        Theme::Light
+impl Theme {
+    fn name(self) -> &'static str {
+        match self {
+            Theme::Light => "light",
+            Theme::Dark => "dark",
+        }
+    }
+    fn from_name(name: &str) -> Option<Theme> {
+        match name {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            _ => None,
+        }
+    }
+    fn toggled(self) -> Theme {
+        match self {
+            Theme::Light => Theme::Dark,
+            Theme::Dark => Theme::Light,
+        }
+    }
+    /// The theme asked by the browser, for wikis where none was chosen yet.
+    fn preferred() -> Theme {
+        let dark = web_sys::window()
+            .and_then(|w| w.match_media("(prefers-color-scheme: dark)").ok().flatten())
+            .is_some_and(|m| m.matches());
+        if dark { Theme::Dark } else { Theme::Light }
     }
 }
 
@@ -51,6 +140,25 @@ fn measure(perf: &web_sys::Performance, name: &str, s: &str, e: &str) {
     }
 }
 
+/// Nested nodes of `i`, or nothing for leaf nodes.
+fn creole_children<'a, 'b>(i: &'b ICreole<'a>) -> &'b [ICreole<'a>] {
+    match i {
+        ICreole::Heading(_, c)
+        | ICreole::Bold(c)
+        | ICreole::Italic(c)
+        | ICreole::Line(c)
+        | ICreole::Table(c)
+        | ICreole::TableHeaderRow(c)
+        | ICreole::TableRow(c)
+        | ICreole::TableHeaderCell(c)
+        | ICreole::TableCell(c)
+        | ICreole::BulletList(c)
+        | ICreole::NumberedList(c)
+        | ICreole::ListItem(c) => c,
+        _ => &[],
+    }
+}
+
 fn creole_filled<'a, G: Html>(cx: Scope<'a>, tag: &str, t: Vec<ICreole<'a>>) -> G {
     let h = G::element_from_tag(tag);
     for c in t {
@@ -72,27 +180,56 @@ fn creole_as_node<'a, G: Html>(cx: Scope<'a>, tag: &str, t: Vec<ICreole<'a>>) ->
 #[component]
 fn CreoleItem<'a, G: Html>(cx: Scope<'a>, i: ICreole<'a>) -> View<G> {
     match i {
-        ICreole::Heading(l, t) => creole_as_node(cx, &format!("h{l}"), t),
+        ICreole::Heading(l, t) => {
+            let h = creole_filled::<G>(cx, &format!("h{l}"), t.clone());
+            h.set_attribute("id", &toc::slug(&toc::heading_text(&t)));
+            View::new_node(h)
+        },
         ICreole::Bold(children) => creole_as_node(cx, "b", children),
         ICreole::Italic(children) => creole_as_node(cx, "i", children),
         ICreole::Text(t) => view! { cx, span { (format!("{t}")) } },
         ICreole::DontFormat(t) => view! { cx, pre { (format!("{t}"))  } },
-        ICreole::Link(href, t) => {
-            if href.starts_with("http://") || href.starts_with("https://") {
-                view! { cx, a(href=href, target="__blank") { (format!("{t}")) } }
-            } else {
-                let path = format!("/w/{}", href);
+        ICreole::Link(href, t) => match use_context::<AppState>(cx).link_policy.get().target(href) {
+            Target::Page(href) => {
+                let section = toc::split(href).1;
+                let Some(href) = links::target_page(href) else {
+                    return toc::section_link(cx, toc::slug(section.unwrap_or_default()), t.to_string());
+                };
+                // tags list the pages carrying them, and links to missing pages open their editor to create them
+                let tag = tags::tag_name(href);
+                let exists = tag.is_some() || href == "help" || use_context::<AppState>(cx).pages.get_untracked().contains(href);
+                let path = match (tag, section) {
+                    (Some(tag), _) => format!("/t/{}", encode_path(tag)),
+                    (None, Some(section)) if exists => format!("/w/{}#{}", encode_path(href), toc::slug(section)),
+                    (None, _) => format!("/{}/{}", if exists { "w" } else { "e" }, encode_path(href)),
+                };
                 let p = path.clone();
                 let on_click = move |e: Event| {
                   e.prevent_default();
-                  sycamore_router::navigate(&p);
+                  navigate_to(&p, false);
                 };
-                view! { cx, a(href=path, on:click=on_click) { (format!("{t}")) } }
+                if exists {
+                    view! { cx, a(href=path, on:click=on_click) { (format!("{t}")) } }
+                } else {
+                    view! { cx,
+                      a(href=path, on:click=on_click, class="missing", title=format!("Create page {href}")) { (format!("{t}")) }
+                    }
+                }
             }
-        }
+            Target::External(href) => view! { cx, a(href=href, target="__blank") { (format!("{t}")) } },
+            Target::App(href) | Target::Script(href) => view! { cx, a(href=href) { (format!("{t}")) } },
+            Target::Blocked(href) => view! { cx,
+              span(class="blocked-link", title=format!("Blocked link to {href}")) { (format!("{t}")) }
+            },
+        },
         ICreole::Line(l) => creole_as_node(cx, "p", l),
         ICreole::Image(src, t) => {
-            if t.is_empty() {
+            if let Target::Blocked(src) = use_context::<AppState>(cx).link_policy.get().image(src) {
+                let text = if t.is_empty() { src } else { t };
+                view! { cx,
+                  span(class="blocked-link", title=format!("Blocked image from {src}")) { (format!("{text}")) }
+                }
+            } else if t.is_empty() {
                 view! { cx, img(src=src) }
             } else {
                 view! { cx, figure {
@@ -130,42 +267,199 @@ fn CreoleItem<'a, G: Html>(cx: Scope<'a>, i: ICreole<'a>) -> View<G> {
     }
 }
 
+/// The text of a block with its tree, kept as spans of the text.
+#[derive(PartialEq)]
+struct BlockTree {
+    text: String,
+    nodes: Vec<tree::Node>,
+}
+
+impl BlockTree {
+    fn parse(text: &str) -> Self {
+        BlockTree { nodes: tree::encode(text, &creoles(text)), text: text.to_owned() }
+    }
+
+    fn tree(&self) -> Vec<ICreole<'_>> {
+        tree::decode(&self.text, &self.nodes)
+    }
+}
+
+/// A block of the previewed source, parsed on its own.
+#[derive(Clone, PartialEq)]
+struct ParsedBlock {
+    /// Hash of the text, and how many blocks up to this one share it.
+    key: (u64, usize),
+    line: usize,
+    block: Rc<BlockTree>,
+}
+
 #[derive(Prop)]
 struct CreolePreviewProps<'a> {
     value: &'a ReadSignal<String>,
     show_title: bool,
+    sync: Option<&'a ScrollSync<'a>>,
     // parsed: &'a ReadSignal<Vec<ICreole<'a>>>,
     // value: Vec<ICreole<'a>>,
 }
 #[component]
 fn CreolePreview<'a, G: Html>(cx: Scope<'a>, props: CreolePreviewProps<'a>) -> View<G> {
-    let vp = create_memo(cx, move || create_ref(cx, props.value.get()));
-    let parsed = create_memo(cx, || {
+    // blocks are parsed once for every distinct text, and rendered once for every key
+    let cache = create_ref(cx, std::cell::RefCell::new(HashMap::<u64, Rc<BlockTree>>::new()));
+    let parsed = create_signal(cx, Vec::<ParsedBlock>::new());
+    // large changes are parsed by a worker, started by the first of them, while the preview shows the text before them
+    let parser = create_ref(cx, parser::Parser::new());
+    let render = move |value: &str| {
         let window = web_sys::window().expect("no global `window` exists");
         let perf = window.performance();
         if let Some(perf) = &perf {
             perf.mark("s2").unwrap_or(());
         }
-        let rst = creoles(*vp.get());
+        let blocks = blocks::split(value);
+        let mut hashes = std::collections::HashSet::new();
+        let missing: Vec<&str> = blocks.iter()
+            .map(|b| b.text)
+            .filter(|t| {
+                let hash = blocks::hash(t);
+                !cache.borrow().contains_key(&hash) && hashes.insert(hash)
+            })
+            .collect();
+        if missing.iter().map(|t| t.len()).sum::<usize>() >= WORKER_THRESHOLD
+            && parser.parse(missing.iter().map(|t| t.to_string()).collect())
+        {
+            return;
+        }
+        let mut cache = cache.borrow_mut();
+        let mut seen = HashMap::<u64, usize>::new();
+        let rst: Vec<ParsedBlock> = blocks
+            .into_iter()
+            .map(|b| {
+                let hash = blocks::hash(b.text);
+                let block = cache.entry(hash).or_insert_with(|| Rc::new(BlockTree::parse(b.text))).clone();
+                let occurrence = seen.entry(hash).or_default();
+                *occurrence += 1;
+                ParsedBlock { key: (hash, *occurrence), line: b.line, block }
+            })
+            .collect();
+        // texts no longer in the source are dropped, as the blocks rendered from them are
+        cache.retain(|hash, _| seen.contains_key(hash));
         if let Some(perf) = &perf {
             perf.mark("e2").unwrap_or(());
-            measure(perf, "creole parse&render", "s2", "e2");
+            // names the share of blocks parsed again, next to the time it took
+            measure(perf, &format!("creole parse {}/{} blocks", missing.len(), rst.len()), "s2", "e2");
         }
-        debug!("parsed  : {:?}", rst);
-        rst
+        parsed.set(rst);
+    };
+    create_effect(cx, move || render(&props.value.get()));
+    create_effect(cx, move || {
+        // results are moved into the cache, and only for blocks the text still has
+        parser.parsed.track();
+        let results = parser.parsed.take_silent();
+        let current: HashSet<u64> = blocks::split(&props.value.get_untracked()).iter().map(|b| blocks::hash(b.text)).collect();
+        {
+            let mut cache = cache.borrow_mut();
+            for (text, nodes) in Rc::unwrap_or_clone(results) {
+                let hash = blocks::hash(&text);
+                if current.contains(&hash) {
+                    cache.insert(hash, Rc::new(BlockTree { text, nodes }));
+                }
+            }
+        }
+        // renders the text as it is by now, which may need more blocks parsed
+        render(&props.value.get_untracked());
+    });
+
+    let toc = create_memo(cx, || {
+        let parsed = parsed.get();
+        let trees: Vec<_> = parsed.iter().map(|b| b.block.tree()).collect();
+        toc::entries(trees.iter().flatten())
     });
+    let placeholder = create_memo(cx, || parsed.get().iter().any(|b| b.block.tree().iter().any(toc::is_placeholder)));
+    let preview_ref = create_node_ref(cx);
+    let blocks_ref = create_node_ref(cx);
+    // blocks are rendered on their own, so headings sharing their text are told apart once all of them are
+    create_effect(cx, move || {
+        let entries = toc.get();
+        spawn_local_scoped(cx, async move {
+            let Some(blocks) = blocks_ref.try_get::<DomNode>() else {
+                return;
+            };
+            let children = blocks.unchecked_into::<web_sys::Element>().children();
+            let headings = (0..children.length())
+                .filter_map(|i| children.item(i))
+                .filter(|e| matches!(e.tag_name().as_str(), "H1" | "H2" | "H3" | "H4" | "H5" | "H6"));
+            for (h, e) in headings.zip(entries.iter()) {
+                h.set_id(&e.slug);
+            }
+        });
+    });
+    // the section named in the location is scrolled to once the page is rendered
+    let scrolled = create_signal(cx, false);
+    create_effect(cx, move || {
+        if !parsed.get().is_empty() && !*scrolled.get_untracked() {
+            scrolled.set(true);
+            wasm_bindgen_futures::spawn_local(async { toc::scroll_to_location() });
+        }
+    });
+
+    let on_scroll = if let Some(sync) = props.sync {
+        let lines = create_memo(cx, || {
+            parsed.get().iter()
+              .flat_map(|b| scroll::block_lines(&b.block.text, &b.block.tree()).into_iter().map(move |l| b.line + l))
+              .collect::<Vec<usize>>()
+        });
+        // scrolling the preview by itself is not followed back by the editor
+        let synced_at = create_signal(cx, 0.);
+        let block = move |i: usize| -> Option<web_sys::HtmlElement> {
+            let blocks = blocks_ref.try_get::<DomNode>()?.unchecked_into::<web_sys::Element>();
+            Some(blocks.children().item(i as u32)?.unchecked_into())
+        };
+        create_effect(cx, move || {
+            let i = scroll::block_at(&lines.get(), *sync.caret.get());
+            if let (Some(preview), Some(b)) = (preview_ref.try_get::<DomNode>(), block(i)) {
+                synced_at.set(Date::now());
+                preview.unchecked_into::<web_sys::Element>().set_scroll_top(b.offset_top());
+            }
+        });
+        Some(move |e: Event| {
+            if Date::now() - *synced_at.get() < 200. {
+                return;
+            }
+            let top = e.target().expect("no preview").unchecked_into::<web_sys::Element>().scroll_top();
+            let lines = lines.get();
+            if let Some(i) = (0..lines.len()).find(|i| block(*i).is_some_and(|b| b.offset_top() >= top)) {
+                sync.preview.set(lines[i]);
+            }
+        })
+    } else {
+        None
+    };
 
     view! { cx,
-      div(class="preview") {
+      div(ref=preview_ref, class="preview", on:scroll=move |e: Event| if let Some(f) = &on_scroll { f(e) }) {
         ( if props.show_title {
             view!{ cx, h2(class="view-name") { "Preview" } }
           } else {
             view!{ cx, } 
           }
         )
-        Indexed {
-          iterable: parsed,
-          view: |cx, x: ICreole| view! { cx, CreoleItem(x) }
+        ( if !*placeholder.get() && toc.get().len() >= toc::TOC_MIN {
+            view! { cx, Toc { entries: toc } }
+          } else {
+            view! { cx, }
+          }
+        )
+        div(ref=blocks_ref, class="blocks") {
+          Keyed {
+            iterable: parsed,
+            view: move |cx, b: ParsedBlock| View::new_fragment(create_ref(cx, b.block).tree().into_iter().map(|i| {
+              if toc::is_placeholder(&i) {
+                view! { cx, Toc { entries: toc } }
+              } else {
+                CreoleItem(cx, i)
+              }
+            }).collect()),
+            key: |b| b.key,
+          }
         }
       }
     }
@@ -174,15 +468,33 @@ fn CreolePreview<'a, G: Html>(cx: Scope<'a>, props: CreolePreviewProps<'a>) -> V
 struct CreoleEditorProps<'a> {
     default: String,
     value: &'a Signal<String>,
+    sync: &'a ScrollSync<'a>,
+    /// Saves the text right away, for when the editor goes away before `value` is set.
+    save: Rc<dyn Fn(&str)>,
+    /// Milliseconds of typing pause before `value` is set.
+    delay: u32,
 }
 const HELP : &str = "= Help
+<<TOC>>
+
 == important note
 Everything you 'edit' in this app stays in your browser(into IndexedDB).
 
 Using any other device, browser, domain, even protocol or clearing your browser cache will erase every note of yourself.
 
+Use **Backup** to export every note into a file regularly and import it back later or elsewhere.
+
 Browsers may limit or ask you for storage expansion when total saved notes are becoming larger than its maximum.
 ----
+== editor shortcuts
+|=Keys|=Does|
+|Ctrl+B|bold|
+|Ctrl+I|italic|
+|Ctrl+K|link|
+|Ctrl+Shift+H|next heading level|
+|Tab / Shift+Tab|indent / outdent list items|
+|Ctrl+Enter|new table row|
+----
 == text styles
 //italic// and **bold**.
 ----
@@ -224,11 +536,28 @@ Browsers may limit or ask you for storage expansion when total saved notes are b
 == script links
 [[javascript:alert('hi')|alert me \"hi\"]]
 
+Script links are blocked unless **Run javascript: links** is checked in **Settings**, as any page imported into the wiki could use them.
+Only http, https, mailto and tel links are followed by default, which can be changed there as well.
+
 == internal links
 [[home]]
 
 [[test]] : view wiki page named \"test\"
 
+== tags
+A line starting with {{{Tags:}}} declares the tags of a page, like {{{Tags: [[tag/howto]], [[tag/draft]]}}}.
+Each tag links to the list of pages carrying it, and every tag is shown on the home page.
+
+== table of contents
+A line holding only {{{<<TOC>>}}} shows the headings of the page there, like at the top of this one.
+Pages without it show them above the page once they have four headings or more.
+
+Each heading starts a section, which [[help#table of contents|links]] name after a {{{#}}} like {{{[[help#table of contents]]}}}, or {{{[[#table of contents]]}}} on the same page.
+
+== redirects
+A page whose first line is {{{#REDIRECT [[home]]}}} opens [[home]] instead, with a notice linking back to the redirecting page to edit it.
+**Move** leaves such a page behind at the old path, unless told not to.
+
 ----
 == headings
 = h1
@@ -268,52 +597,184 @@ Force\\\\linebreak
 #[component]
 fn CreoleEditor<'a, G: Html>(cx: Scope<'a>, props: CreoleEditorProps<'a>) -> View<G> {
     let node_ref = create_node_ref(cx);
+    let highlight_ref = create_node_ref(cx);
+    let highlight = move |value: &str| {
+        if let Some(node) = highlight_ref.try_get::<DomNode>() {
+            node.unchecked_into::<web_sys::Element>().set_inner_html(&highlight::highlight(value));
+        }
+    };
 
-    let last_update = create_signal(cx, 0.);
-    let updated = create_signal(cx, false);
+    let state = use_context::<AppState>(cx);
     let default_value = props.default;
-    {
+    // values set from outside the editor, like imports, replace the text being edited
+    create_effect(cx, move || {
+        let value = props.value.get();
+        if let Some(node) = node_ref.try_get::<DomNode>() {
+            let e: HtmlTextAreaElement = node.unchecked_into();
+            if e.value() != *value {
+                e.set_value(&value);
+                highlight(&value);
+            }
+        }
+    });
+
+    let flush = move || {
+        if !*state.unsaved.get_untracked() {
+            return;
+        }
+        let e: HtmlTextAreaElement = node_ref.get::<DomNode>().unchecked_into();
         let window = web_sys::window().expect("no global `window` exists");
-        spawn_local_scoped(cx, async move {
-            loop {
-                TimeoutFuture::new(500).await;
+        let perf = window.performance();
+        if let Some(perf) = &perf {
+            perf.clear_marks();
+            perf.clear_measures();
+            perf.mark("s1").unwrap_or(());
+        }
+        props.value.set(e.value().into());
 
-                let sec_ago = Date::now() - 1000.;
-                if *updated.get() || *last_update.get() >= sec_ago {
-                    continue;
-                }
-                let node = node_ref.get::<DomNode>();
-                let e: HtmlTextAreaElement = node.unchecked_into();
-
-                let perf = window.performance();
-                if let Some(perf) = &perf {
-                    perf.clear_marks();
-                    perf.clear_measures();
-                    perf.mark("s1").unwrap_or(());
+        if let Some(perf) = &perf {
+            perf.mark("e1").unwrap_or(());
+            measure(perf, "creole input update", "s1", "e1");
+        }
+        state.unsaved.set(false);
+    };
+    // kept out of the scope, to be read while the scope is being disposed
+    let textarea = Rc::new(std::cell::RefCell::new(None::<HtmlTextAreaElement>));
+    // every input restarts the delay, and only the last one flushes
+    let typed = create_signal(cx, 0_u32);
+    let debounce = create_ref(cx, {
+        let textarea = textarea.clone();
+        move || {
+            state.unsaved.set(true);
+            textarea.replace(node_ref.try_get::<DomNode>().map(|n| n.unchecked_into()));
+            typed.set(*typed.get_untracked() + 1);
+            let at = *typed.get_untracked();
+            spawn_local_scoped(cx, async move {
+                TimeoutFuture::new(props.delay).await;
+                if *typed.get_untracked() == at {
+                    flush();
                 }
-                props.value.set(e.value().into());
-
-                if let Some(perf) = &perf {
-                    perf.mark("e1").unwrap_or(());
-                    measure(perf, "creole input update", "s1", "e1");
+            });
+        }
+    });
+    // leaving the page or closing the tab saves what was typed since the last flush
+    let leave = {
+        let state = state.clone();
+        let save = props.save.clone();
+        Rc::new(move || {
+            if *state.unsaved.get_untracked() {
+                if let Some(t) = &*textarea.borrow() {
+                    save(&t.value());
                 }
-                updated.set(true);
-                last_update.set(Date::now());
+                // after the editor is gone, so that its views do not update while being disposed
+                let unsaved = state.unsaved.clone();
+                wasm_bindgen_futures::spawn_local(async move { unsaved.set(false) });
             }
-        });
-    }
+        })
+    };
+    let unload = {
+        let leave = leave.clone();
+        create_ref(cx, Closure::<dyn Fn()>::new(move || leave()))
+    };
+    let window = web_sys::window().expect("no global `window` exists");
+    window.add_event_listener_with_callback("beforeunload", unload.as_ref().unchecked_ref()).unwrap_or(());
+    on_cleanup(cx, move || {
+        window.remove_event_listener_with_callback("beforeunload", unload.as_ref().unchecked_ref()).unwrap_or(());
+        leave();
+    });
 
-    let on_input = |e: Event| {
+    // the preview follows the caret, and the editor follows the preview
+    let on_caret = move |e: Event| {
+        let t: HtmlTextAreaElement = e.target().expect("no textarea").unchecked_into();
+        if let Ok(Some(offset)) = t.selection_start() {
+            props.sync.caret.set(scroll::line_at(&t.value(), offset));
+        }
+    };
+    create_effect(cx, move || {
+        let line = *props.sync.preview.get();
+        if let (Some(t), Some(h)) = (node_ref.try_get::<DomNode>(), highlight_ref.try_get::<DomNode>()) {
+            let lines = h.unchecked_into::<web_sys::Element>().children();
+            if let Some(l) = lines.item(line as u32) {
+                t.unchecked_into::<web_sys::Element>().set_scroll_top(l.unchecked_into::<web_sys::HtmlElement>().offset_top());
+            }
+        }
+    });
+    // formatting commands replace the text and selection of the textarea like typing does
+    let apply = move |action: Action| -> bool {
+        let t: HtmlTextAreaElement = node_ref.get::<DomNode>().unchecked_into();
+        let src = t.value();
+        let (start, end) = match (t.selection_start(), t.selection_end()) {
+            (Ok(Some(s)), Ok(Some(e))) => (format::to_byte(&src, s), format::to_byte(&src, e)),
+            _ => return false,
+        };
+        match action.apply(&src, start, end) {
+            Some((text, start, end)) => {
+                let (start, end) = (format::to_utf16(&text, start), format::to_utf16(&text, end));
+                t.set_value(&text);
+                t.set_selection_range(start, end).unwrap_or(());
+                highlight(&text);
+                props.sync.caret.set(scroll::line_at(&text, start));
+                debounce();
+                true
+            }
+            None => false,
+        }
+    };
+    let on_keydown = move |e: Event| {
+        let e: KeyboardEvent = e.unchecked_into();
+        if let Some(action) = Action::from_key(&e.key(), e.ctrl_key() || e.meta_key(), e.shift_key()) {
+            if apply(action) {
+                e.prevent_default();
+            }
+        }
+    };
+    let toolbar = View::new_fragment(Action::TOOLBAR.iter().map(|a| {
+        let a = *a;
+        let title = format!("{} ({})", a.label(), a.shortcut());
+        view! { cx,
+          button(title=title, on:click=move |_| {
+            apply(a);
+            if let Some(t) = node_ref.try_get::<DomNode>() {
+                t.unchecked_into::<web_sys::HtmlElement>().focus().unwrap_or(());
+            }
+          }) { (a.label()) }
+        }
+    }).collect());
+    let on_input = move |e: Event| {
+        on_caret(e.clone());
+        let t: HtmlTextAreaElement = e.target().expect("no textarea").unchecked_into();
+        highlight(&t.value());
         let e: InputEvent = e.unchecked_into();
-        updated.set(false);
+        debounce();
         debug!("typed : {:?}", e.data());
     };
+    let on_scroll = move |e: Event| {
+        let t: web_sys::Element = e.target().expect("no textarea").unchecked_into();
+        if let Some(node) = highlight_ref.try_get::<DomNode>() {
+            let h: web_sys::Element = node.unchecked_into();
+            h.set_scroll_top(t.scroll_top());
+            h.set_scroll_left(t.scroll_left());
+        }
+    };
+    let highlighted = highlight::highlight(&default_value);
 
     view! { cx,
       div(class="editor") {
-        h2(class="view-name") { "Editor" }
-        textarea(ref=node_ref, on:input=on_input) {
-          (default_value)
+        h2(class="view-name") {
+          "Editor"
+          ( if *state.unsaved.get() {
+              view!{ cx, span(class="unsaved") { "unsaved changes" } }
+            } else {
+              view!{ cx, }
+            }
+          )
+        }
+        div(class="toolbar") { (toolbar) }
+        div(class="editor-area") {
+          pre(ref=highlight_ref, class="highlight", aria-hidden="true", dangerously_set_inner_html=&highlighted)
+          textarea(ref=node_ref, on:input=on_input, on:scroll=on_scroll, on:keydown=on_keydown, on:keyup=on_caret, on:click=on_caret, on:blur=move |_| flush(), spellcheck="false") {
+            (default_value)
+          }
         }
       }
     }
@@ -327,103 +788,251 @@ struct CreoleProps {
 
 #[component]
 async fn Creole<G: Html>(cx: Scope<'_>, props: CreoleProps) -> View<G> {
-    let store = open_db_store(cx, props.editable);
+    let db = use_context::<RcSignal<IdbStore>>(cx).get();
     let path = props.path.clone();
     debug!("getting : {}", path);
 
-    let value =
+    let record =
       if path == "help" {
-          String::from(HELP)
-      } else if let Ok(r) = store.get(&path.into()) {
-        JsFuture::from(js_sys::Promise::new(&mut |resolve : js_sys::Function, _reject: js_sys::Function|{
-          let cb = Closure::once(move |e: &Event| -> Result<(), JsValue> {
-            if let Some(t) = e.target() {
-              resolve.call1(&JsValue::NULL,
-                &t.unchecked_into::<web_sys::IdbRequest>().result().expect("")
-              ).expect("");
-            }
-            Ok(())
-          });
-          r.set_onsuccess(Some(create_ref(cx, cb).as_ref().unchecked_ref()));
-        })).await.expect("promise").as_string().unwrap_or(String::new())
+          None
       } else {
-        String::new()
+        match db.get(&path).await {
+          Ok(record) => record,
+          Err(error) => return view! { cx, StorageFailure { error: error } },
+        }
       };
+    let value = match &record {
+        Some(r) => r.text.clone(),
+        None if path == "help" => String::from(HELP),
+        None => String::new(),
+    };
 
     let value_signal : &Signal<String> = create_signal(cx, value.clone());
 
     if props.editable {
-        let path : JsValue = props.path.clone().into();
-        create_effect(cx, move || {
-          let store = open_db_store(cx, props.editable);
-          let value = &*value_signal.get();
-          let path = path.as_string().unwrap();
+        let path = props.path.clone();
+        let state = use_context::<AppState>(cx).clone();
+        let save: Rc<dyn Fn(&str)> = Rc::new(move |value: &str| {
           debug!("saving to : {}, value : {}", path, value);
-          if path != "help" {
-            if value.is_empty() {
-              if let Ok(_r) = store.delete(&path.into()) {
-              }
-            } else if let Ok(_r) = store.put_with_key(&value.into(), &path.into()) {
+          save_typed_in(&db, &state, &path, value);
+        });
+        let saved = std::cell::RefCell::new(value.clone());
+        {
+          let save = save.clone();
+          create_effect(cx, move || {
+            let value = &*value_signal.get();
+            if *saved.borrow() == *value {
+              return;
             }
-          }
+            save(value);
+            saved.replace(value.clone());
+          });
+        }
+        let sync = create_ref(cx, ScrollSync {
+            caret: create_signal(cx, 0),
+            preview: create_signal(cx, 0),
         });
         view! { cx,
+          MarkdownActions { path: props.path, value: value_signal }
           div(class="wrapper") {
             CreoleEditor {
               value: value_signal,
               default: value,
+              sync: sync,
+              save: save,
+              delay: SAVE_DELAY,
             }
-            CreolePreview{ value :value_signal, show_title: true }
+            CreolePreview{ value :value_signal, show_title: true, sync: Some(sync) }
           }
         }
     } else {
+        let from = query_param("from");
+        if let Some(target) = rename::redirect_target(&value).filter(|_| from.is_none()) {
+            // a redirected page is not followed further, so redirects can not loop
+            let url = format!("/w/{}?from={}", encode_path(target), encode(&props.path));
+            wasm_bindgen_futures::spawn_local(async move { navigate_to(&url, true) });
+        }
+        let notice = match from {
+            Some(from) => {
+                let path = format!("/e/{}", encode_path(&from));
+                let href = path.clone();
+                let on_click = move |e: Event| {
+                    e.prevent_default();
+                    navigate_to(&path, false);
+                };
+                view! { cx,
+                  p(class="redirect-notice") {
+                    "Redirected from "
+                    a(href=href, on:click=on_click) { (format!("/{from}")) }
+                  }
+                }
+            },
+            None => view! { cx, },
+        };
+        let last_edited = match record {
+            Some(record) => view! { cx, LastEdited { record: record } },
+            None => view! { cx, },
+        };
         view! { cx,
-          CreolePreview{ value : value_signal, show_title: false }
+          (notice)
+          CreolePreview{ value : value_signal, show_title: false, sync: None }
+          (last_edited)
+          Backlinks { path: props.path }
         }
     }
 }
 
+/// Bytes of changed blocks from which the preview parses them in a worker.
+const WORKER_THRESHOLD: usize = 64 * 1024;
+/// Milliseconds the editor waits after the last keystroke before saving.
+const SAVE_DELAY: u32 = 1000;
 const DB_NAME: &str = "wiki";
 const STORE_NAME: &str = "wiki";
+const HISTORY_STORE: &str = "history";
+const LINKS_STORE: &str = "links";
+const SEARCH_STORE: &str = "search";
+const SETTINGS_STORE: &str = "settings";
 
-async fn init_db(cx: Scope<'_>) -> IdbDatabase {
-    JsFuture::from(js_sys::Promise::new(&mut |resolve : js_sys::Function, _reject: js_sys::Function|{
-        let window = web_sys::window().expect("no global `window` exists");
-        let req = window
-            .indexed_db().unwrap().expect("user has not enabled IndexedDB")
-            .open(DB_NAME).expect("wiki DB is not available");
-        let a = Closure::once(move |evt: &web_sys::IdbVersionChangeEvent| -> Result<(), JsValue> {
-          if let Some(t) = evt.target() {
-            let wiki_db = t.unchecked_into::<IdbOpenDbRequest>().result().expect("")
-              .unchecked_into::<IdbDatabase>();
-            let wiki_store = wiki_db.create_object_store(STORE_NAME)?;
-            wiki_store.put_with_key(&HELP.into(), &"".into())?;
-          }
-          Ok(())
-        });
-        req.set_onupgradeneeded(Some(a.as_ref().unchecked_ref()));
-        a.forget();
-        let b = Closure::once(move |e: &Event| -> Result<(), JsValue> {
-          if let Some(t) = e.target() {
-            resolve.call1(&JsValue::NULL, &t.unchecked_into::<IdbOpenDbRequest>().result().expect("")).expect("");
-          }
-          Ok(())
-        });
-        req.set_onsuccess(Some(create_ref(cx, b).as_ref().unchecked_ref()));
-    })).await.expect("promise").unchecked_into()
+async fn init_db() -> storage::Result<IdbDatabase> {
+    storage::open(DB_NAME, migrations::VERSION, |db, transaction, from| {
+        migrations::migrate(&mut storage::Upgrade { db, transaction }, from)
+    }).await
+}
+fn open_db_transaction(cx: Scope, stores: &[&str], write: bool) -> storage::Result<IdbTransaction> {
+    use_context::<RcSignal<IdbStore>>(cx).get().transaction(stores, write)
+}
+/// Stores `value` under `path` and appends it as a new revision.
+/// An empty value deletes the page, while its history is kept.
+fn save_page(cx: Scope, path: &str, value: &str) {
+    save_page_in(&use_context::<RcSignal<IdbStore>>(cx).get(), use_context::<AppState>(cx), path, value)
+}
+/// Same as `save_page`, without a scope to find the DB in, like while it is being disposed.
+fn save_page_in(db: &IdbStore, state: &AppState, path: &str, value: &str) {
+    if path == "help" {
+        return;
+    }
+    state.written(path, value);
+    if value.is_empty() {
+        report_failure(state, path, db.delete(path));
+    } else {
+        report_failure(state, path, db.put(path, value));
+    }
+}
+/// Same as `save_page_in` for text typed in the editor, which goes into the revision saved just before
+/// when that one is recent, so that pauses while typing do not each add a revision.
+fn save_typed_in(db: &IdbStore, state: &AppState, path: &str, value: &str) {
+    if path == "help" || value.is_empty() {
+        return save_page_in(db, state, path, value);
+    }
+    state.written(path, value);
+    report_failure(state, path, db.write_all(vec![PageWrite::typed(path, value)], vec![]));
+}
+/// Writes `pages` and `history` together like `PageStore::write_all`, with the paths of the app kept up to date.
+async fn write_pages(cx: Scope<'_>, pages: Vec<PageWrite>, history: Vec<(String, history::Revision)>) -> storage::Result<()> {
+    let state = use_context::<AppState>(cx);
+    for p in &pages {
+        state.written(&p.path, &p.text);
+    }
+    let db = use_context::<RcSignal<IdbStore>>(cx).get();
+    db.write_all(pages, history).await
+}
+/// Shows the user that `saved` failed, as the editor is not there to show it anymore by then.
+fn report_failure(state: &AppState, path: &str, saved: impl std::future::Future<Output = storage::Result<()>> + 'static) {
+    let (path, failure) = (path.to_owned(), state.save_failure.clone());
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Err(e) = saved.await {
+            log::error!("could not save /{} : {}", path, e);
+            failure.set(Some((path, e)));
+        }
+    });
+}
+fn format_time(t: f64) -> String {
+    Date::new(&t.into()).to_locale_string("default", &JsValue::UNDEFINED).into()
+}
+/// Lets the browser save `data` as a file named `name`.
+fn download(name: &str, mime: &str, data: &[u8]) {
+    let bag = web_sys::BlobPropertyBag::new();
+    bag.set_type(mime);
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(data));
+    let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &bag).expect("could not create blob");
+    let url = web_sys::Url::create_object_url_with_blob(&blob).expect("could not create object url");
+    let document = web_sys::window().expect("no global `window` exists").document().expect("no document");
+    let a: web_sys::HtmlAnchorElement = document.create_element("a").expect("could not create anchor").unchecked_into();
+    a.set_href(&url);
+    a.set_download(name);
+    a.click();
+    web_sys::Url::revoke_object_url(&url).unwrap_or(());
+}
+/// Navigates to `url` like `sycamore_router::navigate`, which would route its query or fragment as part of its path.
+/// The location is changed first, then the router reads its path like after going back.
+/// Views made for the same route again see the new query, like searches from the search page.
+fn navigate_to(url: &str, replace: bool) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    if let Ok(history) = window.history() {
+        let changed = if replace {
+            history.replace_state_with_url(&JsValue::UNDEFINED, "", Some(url))
+        } else {
+            history.push_state_with_url(&JsValue::UNDEFINED, "", Some(url))
+        };
+        changed.unwrap_or(());
+    }
+    if !url.contains('#') {
+        window.scroll_to_with_x_and_y(0., 0.);
+    }
+    if let Ok(e) = Event::new("popstate") {
+        window.dispatch_event(&e).unwrap_or(false);
+    }
 }
-fn open_db_store(cx: Scope, write: bool) -> IdbObjectStore {
-    let db = use_context::<RcSignal<IdbDatabase>>(cx);
-    let transaction = db.get().transaction_with_str_and_mode(STORE_NAME, 
-      if write { web_sys::IdbTransactionMode::Readwrite }
-      else { web_sys::IdbTransactionMode::Readonly }).expect("could not open transaction");
-    transaction.object_store(STORE_NAME).expect("could not open store")
+/// Value of the `name` query parameter of the current location.
+fn query_param(name: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    web_sys::UrlSearchParams::new_with_str(&search).ok()?.get(name)
+}
+/// `path` with each of its segments encoded for a URL, as `decode_path` reads them.
+fn encode_path(path: &str) -> String {
+    path.split('/').map(|s| encode(s).into_owned()).collect::<Vec<String>>().join("/")
+}
+fn decode_path(path: &[String]) -> String {
+    path.iter().map(|s| decode(s).expect("UTF8").into_owned()).collect::<Vec<String>>().join("/")
 }
 #[component]
 async fn App<G: Html>(cx: Scope<'_>) -> View<G> {
-    let db = create_rc_signal(init_db(cx).await);
+    let db = match init_db().await {
+        Ok(db) => create_rc_signal(IdbStore::new(db)),
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    };
     debug!("db opened : {:?}, name : {}", db, db.get().name());
-    provide_context(cx, db);
+    provide_context(cx, db.clone());
+
+    // the theme applies to the whole document, nav included
+    let state = use_context::<AppState>(cx);
+    let (theme, policy) = match (settings::get(cx, settings::THEME).await, settings::get(cx, settings::LINK_POLICY).await) {
+        (Ok(theme), Ok(policy)) => (theme, policy),
+        (Err(error), _) | (_, Err(error)) => return view! { cx, StorageFailure { error: error } },
+    };
+    state.theme.set(theme.as_string().and_then(|s| Theme::from_name(&s)).unwrap_or_else(Theme::preferred));
+    match db.get().list().await {
+        Ok(pages) => state.pages.set(pages.into_iter().collect()),
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    }
+    if let Some(policy) = policy.as_string().and_then(|s| serde_json::from_str::<LinkPolicy>(&s).ok()) {
+        state.link_policy.set(policy);
+    }
+    create_effect(cx, move || {
+        let class = format!("theme-{}", state.theme.get().name());
+        if let Some(root) = web_sys::window().and_then(|w| w.document()).and_then(|d| d.document_element()) {
+            root.set_class_name(&class);
+        }
+    });
+    let toggle_theme = move |_| {
+        let theme = state.theme.get().toggled();
+        state.theme.set(theme);
+        if let Err(e) = settings::set(cx, settings::THEME, &theme.name().into()) {
+            log::error!("could not save the theme : {}", e);
+        }
+    };
 
     let wiki_path_node_ref = create_node_ref(cx);
     let wiki_path = create_signal(cx, String::new());
@@ -438,10 +1047,19 @@ async fn App<G: Html>(cx: Scope<'_>) -> View<G> {
         button(on:click=|_| sycamore_router::navigate("/w/")) { ("Home view") }
         button(on:click=|_| sycamore_router::navigate("/e/")) { ("Home edit") }
         button(on:click=|_|sycamore_router::navigate("/help")) { ("Help") }
+        button(on:click=|_|sycamore_router::navigate("/all")) { ("All pages") }
+        button(on:click=|_|sycamore_router::navigate("/backup")) { ("Backup") }
+        button(on:click=|_|sycamore_router::navigate("/settings")) { ("Settings") }
         input(type="text", bind:value=wiki_path, ref=wiki_path_node_ref) { }
         button(on:click=|_| sycamore_router::navigate(&format!("/e/{}", *wiki_path.get()))){ ("Edit") }
         button(on:click=|_| sycamore_router::navigate(&format!("/w/{}", *wiki_path.get()))){ ("View") }
+        button(on:click=|_| sycamore_router::navigate(&format!("/h/{}", *wiki_path.get()))){ ("History") }
+        button(on:click=|_| navigate_to(&format!("/s?q={}", encode(&wiki_path.get())), false)){ ("Search") }
+        button(on:click=|_| sycamore_router::navigate(&format!("/m/{}", *wiki_path.get()))){ ("Move") }
         button(on:click=|_| sycamore_router::navigate(&format!("/d/{}", *wiki_path.get()))){ ("Delete") }
+        button(on:click=toggle_theme) {
+          ( if *state.theme.get() == Theme::Dark { "Light theme" } else { "Dark theme" } )
+        }
       }
       Router {
         integration: HistoryIntegration::new(),
@@ -455,6 +1073,7 @@ async fn App<G: Html>(cx: Scope<'_>) -> View<G> {
                   Index => {
                     view! { cx,
                       Creole{ editable: false, path: set_wiki_path(String::new()) }
+                      TagCloud {}
                     }
                   },
                   Help => {
@@ -463,26 +1082,56 @@ async fn App<G: Html>(cx: Scope<'_>) -> View<G> {
                     }
                   },
                   Wiki{path} => {
-                    let path = path.into_iter().map(|s: &String| decode(s).expect("UTF8").into_owned()).collect::<Vec<String>>();
-                      // decode(&path.join("/")).expect("UTF8").into_owned();
                     view! { cx,
-                      Creole { editable: false, path: set_wiki_path(path.join("/")) }
+                      Creole { editable: false, path: set_wiki_path(decode_path(path)) }
                     }
                   },
                   WikiEdit{path} => {
-                    let path = path.into_iter().map(|s: &String| decode(s).expect("UTF8").into_owned()).collect::<Vec<String>>();
                     view! { cx,
-                      Creole { editable: true, path: set_wiki_path(path.join("/")) }
+                      Creole { editable: true, path: set_wiki_path(decode_path(path)) }
+                    }
+                  },
+                  WikiHistory{path} => {
+                    view! { cx,
+                      History { path: set_wiki_path(decode_path(path)) }
+                    }
+                  },
+                  AllPages => {
+                    view! { cx,
+                      PageList {}
+                    }
+                  },
+                  WikiBackup => {
+                    view! { cx,
+                      Backup {}
+                    }
+                  },
+                  WikiSettings => {
+                    view! { cx,
+                      Settings {}
+                    }
+                  },
+                  WikiSearch => {
+                    view! { cx,
+                      Search { query: query_param("q").unwrap_or_default() }
+                    }
+                  },
+                  WikiTag{tag} => {
+                    view! { cx,
+                      Tagged { tag: decode_path(tag) }
+                    }
+                  },
+                  WikiMove{path} => {
+                    view! { cx,
+                      Rename { path: set_wiki_path(decode_path(path)) }
                     }
                   },
                   WikiDelete{path} => {
-                    let path = path.into_iter().map(|s: &String| decode(s).expect("UTF8").into_owned()).collect::<Vec<String>>();
-                    let p = path.join("/");
+                    let p = decode_path(path);
                     wiki_path.set(p.clone());
-                    let pp : std::cell::RefCell<JsValue> = std::cell::RefCell::new(p.clone().into());
+                    let pp = p.clone();
                     let on_del_yes = move |_| {
-                      let store = open_db_store(cx, true);
-                      if let Ok(_) = store.delete(&pp.take()){}
+                      save_page(cx, &pp, "");
                       sycamore_router::navigate("/");
                     };
                     view! { cx,
@@ -509,11 +1158,19 @@ fn main() {
     console_error_panic_hook::set_once();
     console_log::init_with_level(log::Level::Debug).unwrap();
 
+    let state = AppState::default();
+    update::register(state.waiting.clone());
+
     // sycamore::render(|cx| component(|| App(cx, ())));
-    sycamore::render(|cx| view! { cx,
-      Suspense {
-        fallback: view!{ cx, "Opening DB..."},
-        App {}
-      }
+    sycamore::render(|cx| {
+        provide_context(cx, state);
+        view! { cx,
+          UpdateBanner {}
+          SaveFailure {}
+          Suspense {
+            fallback: view!{ cx, "Opening DB..."},
+            App {}
+          }
+        }
     });
 }
@@ -0,0 +1,326 @@
+//! Conversion between Creole and CommonMark (with GitHub flavored tables).
+
+use creole_nom::prelude::*;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Event, HtmlInputElement};
+
+use crate::download;
+
+fn escape(t: &str) -> String {
+    let mut rst = String::with_capacity(t.len());
+    for c in t.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']') {
+            rst.push('\\');
+        }
+        rst.push(c);
+    }
+    rst
+}
+
+fn inline(tree: &[ICreole]) -> String {
+    tree.iter().map(|i| match i {
+        ICreole::Text(t) => escape(t),
+        ICreole::Bold(c) => format!("**{}**", inline(c)),
+        ICreole::Italic(c) => format!("*{}*", inline(c)),
+        ICreole::DontFormat(t) => {
+            let fence = if t.contains('`') { "``" } else { "`" };
+            format!("{fence}{t}{fence}")
+        }
+        ICreole::Link(href, t) => format!("[{}]({})", escape(t), href.replace(' ', "%20")),
+        ICreole::Image(src, t) => format!("![{}]({})", escape(t), src.replace(' ', "%20")),
+        ICreole::Silentbreak => String::from("\n"),
+        ICreole::ForceLinebreak => String::from("\\\n"),
+        _ => to_markdown(std::slice::from_ref(i)),
+    }).collect()
+}
+
+fn list(out: &mut String, items: &[ICreole], numbered: bool, depth: usize) {
+    for i in items {
+        match i {
+            ICreole::ListItem(c) => {
+                let marker = if numbered { "1." } else { "-" };
+                out.push_str(&format!("{}{marker} {}\n", "   ".repeat(depth), inline(c).trim()));
+            }
+            ICreole::BulletList(c) => list(out, c, false, depth + 1),
+            ICreole::NumberedList(c) => list(out, c, true, depth + 1),
+            _ => {}
+        }
+    }
+}
+
+fn table_row(cells: &[ICreole]) -> String {
+    let cells: Vec<String> = cells.iter().map(|c| match c {
+        ICreole::TableHeaderCell(c) | ICreole::TableCell(c) => inline(c).trim().replace('|', "\\|").replace('\n', " "),
+        _ => String::new(),
+    }).collect();
+    format!("| {} |\n", cells.join(" | "))
+}
+
+/// Writes `tree` as CommonMark, with tables as GitHub flavored pipe tables.
+pub fn to_markdown(tree: &[ICreole]) -> String {
+    let mut out = String::new();
+    for i in tree {
+        match i {
+            ICreole::Heading(l, t) => out.push_str(&format!("{} {}\n\n", "#".repeat(*l as usize), inline(t).trim())),
+            ICreole::Line(l) => {
+                let l = inline(l);
+                if !l.trim().is_empty() {
+                    out.push_str(l.trim_matches('\n'));
+                    out.push_str("\n\n");
+                }
+            }
+            ICreole::DontFormat(t) => out.push_str(&format!("```\n{}\n```\n\n", t.trim_matches('\n'))),
+            ICreole::HorizontalLine => out.push_str("---\n\n"),
+            ICreole::BulletList(c) => {
+                list(&mut out, c, false, 0);
+                out.push('\n');
+            }
+            ICreole::NumberedList(c) => {
+                list(&mut out, c, true, 0);
+                out.push('\n');
+            }
+            ICreole::Table(rows) => {
+                let mut rows = rows.iter().filter_map(|r| match r {
+                    ICreole::TableHeaderRow(c) | ICreole::TableRow(c) => Some(c),
+                    _ => None,
+                });
+                if let Some(head) = rows.next() {
+                    out.push_str(&table_row(head));
+                    out.push_str(&format!("|{}\n", " --- |".repeat(head.len())));
+                    for r in rows {
+                        out.push_str(&table_row(r));
+                    }
+                    out.push('\n');
+                }
+            }
+            _ => out.push_str(&inline(std::slice::from_ref(i))),
+        }
+    }
+    out
+}
+
+/// Converts inline Markdown of one line into Creole.
+fn inline_creole(md: &str) -> String {
+    let mut out = String::new();
+    let mut rest = md;
+    while let Some(c) = rest.chars().next() {
+        let next = &rest[c.len_utf8()..];
+        if c == '\\' && next.starts_with(|c: char| c.is_ascii_punctuation()) {
+            let e = next.chars().next().map_or(0, |c| c.len_utf8());
+            match &next[..e] {
+                // characters with a meaning in Creole stay escaped from it
+                "*" | "/" | "[" | "{" | "|" | "=" | "#" | "\\" => out.push_str(&format!("{{{{{{{}}}}}}}", &next[..e])),
+                p => out.push_str(p),
+            }
+            rest = &next[e..];
+        } else if c == '`' {
+            let ticks = rest.len() - rest.trim_start_matches('`').len();
+            let fence = &rest[..ticks];
+            match rest[ticks..].find(fence) {
+                Some(end) => {
+                    out.push_str(&format!("{{{{{{{}}}}}}}", rest[ticks..ticks + end].trim()));
+                    rest = &rest[ticks + end + ticks..];
+                }
+                None => {
+                    out.push_str(fence);
+                    rest = &rest[ticks..];
+                }
+            }
+        } else if let Some((text, url, len)) = md_link(rest.strip_prefix('!').unwrap_or(rest)) {
+            let image = rest.starts_with('!');
+            let url = url.replace("%20", " ");
+            if image {
+                out.push_str(&format!("{{{{{url}|{text}}}}}"));
+            } else if text.is_empty() || text == url {
+                out.push_str(&format!("[[{url}]]"));
+            } else {
+                out.push_str(&format!("[[{url}|{}]]", inline_creole(text)));
+            }
+            rest = &rest[len + usize::from(image)..];
+        } else if c == '<' && (next.starts_with("http://") || next.starts_with("https://")) && next.contains('>') {
+            let end = next.find('>').unwrap_or(0);
+            out.push_str(&format!("[[{}]]", &next[..end]));
+            rest = &next[end + 1..];
+        } else if rest.starts_with("**") || rest.starts_with("__") {
+            out.push_str("**");
+            rest = &rest[2..];
+        } else if (c == '*' || c == '_') && is_emphasis(&out, next) {
+            out.push_str("//");
+            rest = next;
+        } else {
+            out.push(c);
+            rest = next;
+        }
+    }
+    out
+}
+
+/// Whether a lone `*` or `_` between `before` and `after` marks emphasis,
+/// rather than standing inside a word like in `snake_case` or on its own like in `a * b`.
+fn is_emphasis(before: &str, after: &str) -> bool {
+    let (prev, next) = (before.chars().next_back(), after.chars().next());
+    let word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    let space = |c: Option<char>| c.is_none_or(char::is_whitespace);
+    !(word(prev) && word(next) || space(prev) && space(next))
+}
+
+/// `[text](url)` at the start of `s` as its text, url and length.
+fn md_link(s: &str) -> Option<(&str, &str, usize)> {
+    let s = s.strip_prefix('[')?;
+    let text_end = s.find("](")?;
+    let url_end = s[text_end + 2..].find(')')? + text_end + 2;
+    let url = s[text_end + 2..url_end].split_whitespace().next().unwrap_or_default();
+    Some((&s[..text_end], url, url_end + 2))
+}
+
+fn list_marker(line: &str) -> Option<(usize, bool, &str)> {
+    let indent = line.len() - line.trim_start().len();
+    let t = line.trim_start();
+    if let Some(rest) = t.strip_prefix("- ").or_else(|| t.strip_prefix("* ")).or_else(|| t.strip_prefix("+ ")) {
+        return Some((indent, false, rest));
+    }
+    let digits = t.len() - t.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits > 0 {
+        if let Some(rest) = t[digits..].strip_prefix(". ").or_else(|| t[digits..].strip_prefix(") ")) {
+            return Some((indent, true, rest));
+        }
+    }
+    None
+}
+
+fn table_cells(line: &str) -> Vec<&str> {
+    let t = line.trim();
+    let t = t.strip_prefix('|').unwrap_or(t);
+    let t = t.strip_suffix('|').unwrap_or(t);
+    t.split('|').map(str::trim).collect()
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let t = line.trim();
+    t.contains('-') && t.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// Converts Markdown source into Creole source.
+pub fn from_markdown(md: &str) -> String {
+    let lines: Vec<&str> = md.lines().collect();
+    let mut out: Vec<String> = vec![];
+    // marker prefixes of the open list levels, with the indentation they started at
+    let mut levels: Vec<(usize, char)> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let t = line.trim();
+        if list_marker(line).is_none() && !t.is_empty() && !line.starts_with(' ') {
+            levels.clear();
+        }
+        if t.starts_with("```") || t.starts_with("~~~") {
+            let fence = &t[..3];
+            out.push(String::from("{{{"));
+            i += 1;
+            while i < lines.len() && !lines[i].trim().starts_with(fence) {
+                out.push(lines[i].to_string());
+                i += 1;
+            }
+            out.push(String::from("}}}"));
+        } else if let Some(level) = (1..=6).rev().find(|l| t.starts_with(&format!("{} ", "#".repeat(*l)))) {
+            out.push(format!("{} {}", "=".repeat(level), inline_creole(t[level..].trim().trim_end_matches('#').trim())));
+        } else if t.len() >= 3 && (t.chars().all(|c| c == '-') || t.chars().all(|c| c == '*') || t.chars().all(|c| c == '_')) {
+            out.push(String::from("----"));
+        } else if let Some((indent, numbered, rest)) = list_marker(line) {
+            while levels.last().is_some_and(|(l, _)| *l > indent) {
+                levels.pop();
+            }
+            let marker = if numbered { '#' } else { '*' };
+            match levels.last_mut() {
+                Some((l, m)) if *l == indent => *m = marker,
+                _ => levels.push((indent, marker)),
+            }
+            let prefix: String = levels.iter().map(|(_, m)| m).collect();
+            out.push(format!("{prefix} {}", inline_creole(rest)));
+        } else if t.starts_with('|') && lines.get(i + 1).is_some_and(|l| is_table_separator(l)) {
+            out.push(format!("|={}|", table_cells(t).iter().map(|c| inline_creole(c)).collect::<Vec<_>>().join("|=")));
+            i += 1;
+            while i + 1 < lines.len() && lines[i + 1].trim().starts_with('|') {
+                i += 1;
+                out.push(format!("|{}|", table_cells(lines[i]).iter().map(|c| inline_creole(c)).collect::<Vec<_>>().join("|")));
+            }
+        } else if let Some(hard) = line.strip_suffix("  ").or_else(|| line.strip_suffix('\\')) {
+            out.push(format!("{}\\\\", inline_creole(hard.trim_end())));
+        } else {
+            out.push(inline_creole(t));
+        }
+        i += 1;
+    }
+    out.join("\n")
+}
+
+#[derive(Prop)]
+pub struct MarkdownActionsProps<'a> {
+    path: String,
+    value: &'a Signal<String>,
+}
+
+/// "Export .md" and "Import .md" buttons of the edit page.
+#[component]
+pub fn MarkdownActions<'a, G: Html>(cx: Scope<'a>, props: MarkdownActionsProps<'a>) -> View<G> {
+    let name = match props.path.rsplit('/').next() {
+        Some(n) if !n.is_empty() => format!("{n}.md"),
+        _ => String::from("home.md"),
+    };
+    let value = props.value;
+    let on_export = move |_| download(&name, "text/markdown", to_markdown(&creoles(&value.get())).as_bytes());
+    let on_import = move |e: Event| {
+        let input: HtmlInputElement = e.target().expect("no input").unchecked_into();
+        if let Some(file) = input.files().and_then(|f| f.get(0)) {
+            spawn_local_scoped(cx, async move {
+                let Some(md) = JsFuture::from(file.text()).await.ok().and_then(|t| t.as_string()) else {
+                    return;
+                };
+                // the page is replaced as a whole, which is only done once asked for
+                let question = format!("Replace the text of this page with {}?", file.name());
+                let replace = value.get().is_empty()
+                    || web_sys::window().is_some_and(|w| w.confirm_with_message(&question).unwrap_or(false));
+                if replace {
+                    value.set(from_markdown(&md));
+                }
+                input.set_value("");
+            });
+        }
+    };
+    view! { cx,
+      div(class="actions") {
+        button(on:click=on_export) { "Export .md" }
+        label {
+          "Import .md "
+          input(type="file", accept=".md,.markdown,text/markdown", on:change=on_import)
+        }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creole_to_markdown() {
+        assert_eq!(to_markdown(&creoles("= t\n**b** //i//")), "# t\n\n**b** *i*\n\n");
+        assert_eq!(to_markdown(&creoles("* a\n** aa\n# 1")), "- a\n   - aa\n\n1. 1\n\n");
+        assert_eq!(to_markdown(&creoles("[[home]] {{a.png|A}}")), "[home](home) ![A](a.png)\n\n");
+        assert_eq!(to_markdown(&creoles("|=a|=b|\n|c|d|")), "| a | b |\n| --- | --- |\n| c | d |\n\n");
+        assert_eq!(to_markdown(&creoles("{{{\n**x**\n}}}")), "```\n**x**\n```\n\n");
+    }
+
+    #[test]
+    fn markdown_to_creole() {
+        assert_eq!(from_markdown("## t\n**b** *i* _j_"), "== t\n**b** //i// //j//");
+        assert_eq!(from_markdown("- a\n  - aa\n    1. x\n- b"), "* a\n** aa\n**# x\n* b");
+        assert_eq!(from_markdown("[W](https://w.org) [x](x) ![A](a.png)"), "[[https://w.org|W]] [[x]] {{a.png|A}}");
+        assert_eq!(from_markdown("| a | b |\n|---|---|\n| c | d |"), "|=a|=b|\n|c|d|");
+        assert_eq!(from_markdown("```\n# x\n```\n`y`"), "{{{\n# x\n}}}\n{{{y}}}");
+        assert_eq!(from_markdown("snake_case ---\n***"), "snake_case ---\n----");
+        assert_eq!(from_markdown("a * b, 2*3*4 and *i* _j_"), "a * b, 2*3*4 and //i// //j//");
+    }
+}
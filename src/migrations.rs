@@ -0,0 +1,229 @@
+//! Numbered steps bringing the DB of any earlier version of the app to the current one.
+
+use creole_nom::prelude::*;
+use serde_json::Value;
+
+use crate::record::PageRecord;
+use crate::storage::Result;
+use crate::{links, search, tags, HELP, HISTORY_STORE, LINKS_STORE, SEARCH_STORE, SETTINGS_STORE, STORE_NAME};
+
+/// Record put by a migration, as `(store, key, value)`.
+pub type Put = (&'static str, String, Value);
+
+/// Records derived from the key and value of another one.
+pub type Derive = Box<dyn FnMut(&str, &Value) -> Vec<Put>>;
+
+/// What migrations change in a DB, kept apart from IndexedDB so they can be tested.
+pub trait Schema {
+    fn create_store(&mut self, name: &str) -> Result<()>;
+    /// Creates an index of `store` on `key_path`, with an entry per item of an array when `multi_entry`.
+    fn create_index(&mut self, store: &str, name: &str, key_path: &str, multi_entry: bool) -> Result<()>;
+    fn put(&mut self, store: &str, key: &str, value: Value) -> Result<()>;
+    /// Puts the records `f` makes of the key and value of every record in `store`.
+    fn for_each(&mut self, store: &str, f: Derive) -> Result<()>;
+}
+
+pub type Migration = fn(&mut dyn Schema) -> Result<()>;
+
+/// Version `n` of the DB is the one left by the first `n` migrations.
+/// Migrations are only ever appended, as a DB is never migrated twice to the same version.
+pub const MIGRATIONS: &[Migration] = &[
+    create_wiki,
+    add_history,
+    add_links,
+    add_search,
+    add_settings,
+    structure_pages,
+    index_tags,
+    reindex_links,
+];
+
+/// Version of the DB this app works with.
+pub const VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Runs every migration after version `from`.
+pub fn migrate(db: &mut dyn Schema, from: u32) -> Result<()> {
+    for m in MIGRATIONS.iter().skip(from as usize) {
+        m(db)?;
+    }
+    Ok(())
+}
+
+/// Text of a page, stored as a record or before as bare text, and `None` for anything else.
+/// Migrations reading pages take both, as later ones run while they read.
+fn page_text(value: &Value) -> Option<&str> {
+    value.as_str().or_else(|| value.get("text")?.as_str())
+}
+
+fn create_wiki(db: &mut dyn Schema) -> Result<()> {
+    db.create_store(STORE_NAME)?;
+    db.put(STORE_NAME, "", Value::from(HELP))
+}
+
+fn add_history(db: &mut dyn Schema) -> Result<()> {
+    db.create_store(HISTORY_STORE)
+}
+
+fn add_links(db: &mut dyn Schema) -> Result<()> {
+    links::create_store(db)?;
+    db.for_each(STORE_NAME, Box::new(|path, value| {
+        page_text(value).and_then(links::record).map(|r| (LINKS_STORE, path.to_owned(), r)).into_iter().collect()
+    }))
+}
+
+fn add_search(db: &mut dyn Schema) -> Result<()> {
+    search::create_store(db)?;
+    db.for_each(STORE_NAME, Box::new(|path, value| {
+        page_text(value).and_then(|t| search::record(path, t)).map(|r| (SEARCH_STORE, path.to_owned(), r)).into_iter().collect()
+    }))
+}
+
+fn add_settings(db: &mut dyn Schema) -> Result<()> {
+    db.create_store(SETTINGS_STORE)
+}
+
+fn structure_pages(db: &mut dyn Schema) -> Result<()> {
+    db.for_each(STORE_NAME, Box::new(|path, value| {
+        PageRecord::from_value(value.clone()).map(|r| (STORE_NAME, path.to_owned(), r.to_value())).into_iter().collect()
+    }))
+}
+
+fn index_tags(db: &mut dyn Schema) -> Result<()> {
+    tags::create_index(db)?;
+    db.for_each(STORE_NAME, Box::new(|path, value| {
+        let record = PageRecord::from_value(value.clone()).map(|r| PageRecord { tags: tags::tags(&creoles(&r.text)), ..r });
+        record.map(|r| (STORE_NAME, path.to_owned(), r.to_value())).into_iter().collect()
+    }))
+}
+
+/// Links were indexed by their raw target, spaces and section included, before they were by page.
+fn reindex_links(db: &mut dyn Schema) -> Result<()> {
+    db.for_each(STORE_NAME, Box::new(|path, value| {
+        page_text(value).and_then(links::record).map(|r| (LINKS_STORE, path.to_owned(), r)).into_iter().collect()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::record::word_count;
+    use crate::storage::StorageError;
+
+    /// A DB held in memory, for tests.
+    #[derive(Debug, Default)]
+    struct MemoryDb {
+        stores: BTreeMap<String, BTreeMap<String, Value>>,
+        /// Indexes as `(store, name, key_path, multi_entry)`.
+        indexes: Vec<(String, String, String, bool)>,
+    }
+
+    impl Schema for MemoryDb {
+        /// Fails on a store that exists already, like IndexedDB does.
+        fn create_store(&mut self, name: &str) -> Result<()> {
+            if self.stores.contains_key(name) {
+                return Err(StorageError::Failed(format!("{name} already exists (ConstraintError)")));
+            }
+            self.stores.insert(name.to_owned(), BTreeMap::new());
+            Ok(())
+        }
+        fn create_index(&mut self, store: &str, name: &str, key_path: &str, multi_entry: bool) -> Result<()> {
+            self.indexes.push((store.to_owned(), name.to_owned(), key_path.to_owned(), multi_entry));
+            Ok(())
+        }
+        fn put(&mut self, store: &str, key: &str, value: Value) -> Result<()> {
+            self.stores.get_mut(store).expect("no such store").insert(key.to_owned(), value);
+            Ok(())
+        }
+        fn for_each(&mut self, store: &str, mut f: Derive) -> Result<()> {
+            let puts: Vec<Put> = self.stores[store].iter().flat_map(|(k, v)| f(k, v)).collect();
+            for (store, key, value) in puts {
+                self.put(store, &key, value)?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn from_first_version() {
+        let mut db = MemoryDb::default();
+        MIGRATIONS[0](&mut db).expect("first migration");
+        db.put(STORE_NAME, "a", Value::from("see [[b]] and [[http://c]]")).unwrap();
+        migrate(&mut db, 1).expect("later migrations");
+
+        assert_eq!(db.stores.keys().collect::<Vec<_>>(), [HISTORY_STORE, LINKS_STORE, SEARCH_STORE, SETTINGS_STORE, STORE_NAME]);
+        assert_eq!(db.stores[STORE_NAME][""], PageRecord::legacy(HELP).to_value());
+        assert_eq!(db.stores[LINKS_STORE]["a"], serde_json::json!({ "targets": ["b"] }));
+        let tokens = &db.stores[SEARCH_STORE]["a"]["tokens"];
+        assert!(tokens.as_array().unwrap().contains(&Value::from("see")));
+        assert!(db.indexes.iter().all(|i| i.3));
+    }
+
+    #[test]
+    fn reindexes_links() {
+        let mut db = MemoryDb::default();
+        // the version `reindex_links` upgrades from
+        let before = 7;
+        for m in &MIGRATIONS[..before] {
+            m(&mut db).unwrap();
+        }
+        db.put(STORE_NAME, "a", PageRecord::legacy("[[ b ]] [[c#intro]]").to_value()).unwrap();
+        db.put(LINKS_STORE, "a", serde_json::json!({ "targets": [" b ", "c#intro"] })).unwrap();
+        migrate(&mut db, before as u32).unwrap();
+        assert_eq!(db.stores[LINKS_STORE]["a"], serde_json::json!({ "targets": ["b", "c"] }));
+    }
+
+    #[test]
+    fn creates_stores_once() {
+        let mut db = MemoryDb::default();
+        migrate(&mut db, 0).unwrap();
+        assert!(migrate(&mut db, 1).is_err());
+    }
+
+    /// Writes page `a` as the DB at version `version` stored it, with the records derived from it.
+    fn write_page(db: &mut MemoryDb, version: u32, text: &str) {
+        let page = match version {
+            ..=5 => Value::from(text),
+            6 => serde_json::json!({ "text": text, "created": 1.0, "updated": 2.0, "words": word_count(text) }),
+            _ => serde_json::json!({ "text": text, "created": 1.0, "updated": 2.0, "words": word_count(text), "tags": ["t"] }),
+        };
+        db.put(STORE_NAME, "a", page).unwrap();
+        if version >= 3 {
+            // targets were indexed as written before they were trimmed
+            let links = match version {
+                ..=7 => serde_json::json!({ "targets": [" b ", "tag/t"] }),
+                _ => links::record(text).unwrap(),
+            };
+            db.put(LINKS_STORE, "a", links).unwrap();
+        }
+        if version >= 4 {
+            db.put(SEARCH_STORE, "a", search::record("a", text).unwrap()).unwrap();
+        }
+    }
+
+    #[test]
+    fn from_every_version() {
+        let text = "see [[ b ]]\n\nTags: [[tag/t]]";
+        let mut latest = MemoryDb::default();
+        migrate(&mut latest, 0).unwrap();
+        for from in 1..=VERSION {
+            let mut db = MemoryDb::default();
+            for m in &MIGRATIONS[..from as usize] {
+                m(&mut db).unwrap();
+            }
+            write_page(&mut db, from, text);
+            migrate(&mut db, from).unwrap();
+
+            assert!(db.stores.keys().eq(latest.stores.keys()), "from version {from}");
+            assert_eq!(db.indexes, latest.indexes, "from version {from}");
+            let times = if from >= 6 { (Some(1.), Some(2.)) } else { (None, None) };
+            let record = PageRecord { created: times.0, updated: times.1, ..PageRecord::legacy(text) };
+            assert_eq!(record.tags, ["t"]);
+            assert_eq!(db.stores[STORE_NAME]["a"], record.to_value(), "from version {from}");
+            assert_eq!(db.stores[STORE_NAME][""], latest.stores[STORE_NAME][""], "from version {from}");
+            assert_eq!(Some(&db.stores[LINKS_STORE]["a"]), links::record(text).as_ref(), "from version {from}");
+            assert_eq!(Some(&db.stores[SEARCH_STORE]["a"]), search::record("a", text).as_ref(), "from version {from}");
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use sycamore::prelude::*;
+use web_sys::Event;
+
+use crate::storage::{self, IdbStore, PageStore, StorageFailure};
+use crate::{encode_path, format_time};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageInfo {
+    pub path: String,
+    pub size: usize,
+    /// Time of the latest save, as its record keeps it.
+    pub modified: Option<f64>,
+}
+
+/// Every page in the store with its size and last modification time.
+pub async fn pages(cx: Scope<'_>) -> storage::Result<Vec<PageInfo>> {
+    let records = use_context::<RcSignal<IdbStore>>(cx).get().records().await?;
+    Ok(records.into_iter()
+      .map(|(path, r)| PageInfo { size: r.text.len(), modified: r.updated, path })
+      .collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Name,
+    Size,
+    Modified,
+}
+
+impl Sort {
+    fn from_str(s: &str) -> Self {
+        match s {
+            "size" => Sort::Size,
+            "modified" => Sort::Modified,
+            _ => Sort::Name,
+        }
+    }
+    fn sort(self, pages: &mut [&PageInfo]) {
+        match self {
+            Sort::Name => pages.sort_by(|a, b| a.path.cmp(&b.path)),
+            Sort::Size => pages.sort_by_key(|p| std::cmp::Reverse(p.size)),
+            Sort::Modified => pages.sort_by(|a, b| b.modified.unwrap_or(0.).total_cmp(&a.modified.unwrap_or(0.))),
+        }
+    }
+}
+
+/// A level of the page hierarchy split at `/`.
+#[derive(Debug, Default)]
+struct Node<'a> {
+    name: &'a str,
+    page: Option<&'a PageInfo>,
+    children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    /// Groups `pages` by path segments, keeping the order in which they are given.
+    fn tree(pages: &[&'a PageInfo]) -> Vec<Node<'a>> {
+        let mut root = Node::default();
+        for p in pages {
+            let mut node = &mut root;
+            for name in p.path.split('/') {
+                let i = match node.children.iter().position(|c| c.name == name) {
+                    Some(i) => i,
+                    None => {
+                        node.children.push(Node { name, ..Default::default() });
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[i];
+            }
+            node.page = Some(p);
+        }
+        root.children
+    }
+}
+
+fn node_view<G: Html>(cx: Scope, node: &Node) -> View<G> {
+    let name = if node.name.is_empty() { String::from("(home)") } else { node.name.to_string() };
+    let title = match node.page {
+        Some(p) => {
+            let path = format!("/w/{}", encode_path(&p.path));
+            let href = path.clone();
+            let on_click = move |e: Event| {
+                e.prevent_default();
+                sycamore_router::navigate(&path);
+            };
+            let info = format!(" {} bytes{}", p.size, p.modified.map(|t| format!(", {}", format_time(t))).unwrap_or_default());
+            view! { cx,
+              a(href=href, on:click=on_click) { (name) }
+              span(class="page-info") { (info) }
+            }
+        }
+        None => view! { cx, span { (name) } },
+    };
+    let children = if node.children.is_empty() {
+        view! { cx, }
+    } else {
+        let children = View::new_fragment(node.children.iter().map(|c| node_view(cx, c)).collect());
+        view! { cx, ul { (children) } }
+    };
+    view! { cx,
+      li {
+        (title)
+        (children)
+      }
+    }
+}
+
+#[component]
+pub async fn PageList<G: Html>(cx: Scope<'_>) -> View<G> {
+    let pages = match pages(cx).await {
+        Ok(pages) => create_ref(cx, pages),
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    };
+    let filter = create_signal(cx, String::new());
+    let sort = create_signal(cx, String::from("name"));
+
+    let list = create_memo(cx, move || {
+        let filter = filter.get().to_lowercase();
+        let mut shown: Vec<&PageInfo> = pages.iter().filter(|p| p.path.to_lowercase().contains(&filter)).collect();
+        Sort::from_str(&sort.get()).sort(&mut shown);
+        shown
+    });
+
+    view! { cx,
+      div(class="pages") {
+        h2(class="view-name") { (format!("{} pages", pages.len())) }
+        div(class="controls") {
+          input(type="search", placeholder="filter", bind:value=filter)
+          select(bind:value=sort) {
+            option(value="name") { "Name" }
+            option(value="size") { "Size" }
+            option(value="modified") { "Last modified" }
+          }
+        }
+        ul {
+          ({
+            let list = list.get();
+            View::new_fragment(Node::tree(&list).iter().map(|n| node_view(cx, n)).collect())
+          })
+        }
+      }
+    }
+}
@@ -0,0 +1,158 @@
+//! Sends blocks to the parser worker, one request at a time.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use gloo_timers::future::TimeoutFuture;
+use sycamore::prelude::*;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use web_sys::{MessageEvent, Worker};
+
+use crate::tree::{Node, Reply, Request};
+
+/// Script loading the worker built from `src/bin/parser.rs`.
+const WORKER_URL: &str = "/parser_loader.js";
+/// Milliseconds a request may take, loading the worker included, before the page parses on its own.
+const TIMEOUT: u32 = 5000;
+
+type Parsed = RcSignal<Vec<(String, Vec<Node>)>>;
+
+#[derive(Default)]
+struct Requests {
+    next: u32,
+    /// Request being parsed, or waiting for the worker to be ready.
+    sent: Option<(u32, Vec<String>)>,
+    ready: bool,
+    failed: bool,
+}
+
+/// The worker, with the closures it calls.
+struct Started {
+    worker: Worker,
+    _on_message: Closure<dyn Fn(MessageEvent)>,
+    _on_error: Closure<dyn Fn()>,
+}
+
+pub struct Parser {
+    /// Started by the first request, so that pages never parsed in it do not load it.
+    started: RefCell<Option<Started>>,
+    requests: Rc<RefCell<Requests>>,
+    /// Blocks of the last response with their trees.
+    /// Emptied when the worker fails, to parse on the main thread again.
+    pub parsed: Parsed,
+}
+
+fn post(worker: &Worker, id: u32, blocks: &[String]) -> Result<(), JsValue> {
+    let json = serde_json::to_string(&Request { id, blocks: blocks.to_vec() }).expect("could not serialize blocks");
+    worker.post_message(&json.into())
+}
+
+/// Stops using `worker`, so that what is waiting for it is parsed on the main thread.
+fn fail(worker: &Worker, requests: &RefCell<Requests>, parsed: &Parsed, reason: &str) {
+    log::error!("parser worker failed, parsing on the main thread: {reason}");
+    worker.terminate();
+    {
+        let mut requests = requests.borrow_mut();
+        requests.failed = true;
+        requests.sent = None;
+    }
+    parsed.set(vec![]);
+}
+
+impl Parser {
+    pub fn new() -> Parser {
+        Parser { started: RefCell::new(None), requests: Rc::default(), parsed: create_rc_signal(vec![]) }
+    }
+
+    fn start(&self) -> Option<Started> {
+        let worker = Worker::new(WORKER_URL).ok()?;
+        let on_message = {
+            let (worker, requests, parsed) = (worker.clone(), self.requests.clone(), self.parsed.clone());
+            Closure::<dyn Fn(MessageEvent)>::new(move |e: MessageEvent| {
+                let reply = match e.data().as_string().map(|s| serde_json::from_str::<Reply>(&s)) {
+                    Some(Ok(r)) => r,
+                    _ => Reply::Failed { id: None, reason: String::from("unreadable reply") },
+                };
+                match reply {
+                    Reply::Ready => {
+                        let posted = {
+                            let mut requests = requests.borrow_mut();
+                            requests.ready = true;
+                            match &requests.sent {
+                                Some((id, blocks)) => post(&worker, *id, blocks),
+                                None => Ok(()),
+                            }
+                        };
+                        if let Err(e) = posted {
+                            fail(&worker, &requests, &parsed, &format!("{e:?}"));
+                        }
+                    }
+                    Reply::Parsed { id, trees } => {
+                        let sent = requests.borrow_mut().sent.take();
+                        if let Some((sent, blocks)) = sent {
+                            if sent == id {
+                                parsed.set(blocks.into_iter().zip(trees).collect());
+                            }
+                        }
+                    }
+                    Reply::Failed { reason, .. } => fail(&worker, &requests, &parsed, &reason),
+                }
+            })
+        };
+        let on_error = {
+            let (worker, requests, parsed) = (worker.clone(), self.requests.clone(), self.parsed.clone());
+            Closure::<dyn Fn()>::new(move || fail(&worker, &requests, &parsed, "error event"))
+        };
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        worker.set_onerror(Some(on_error.as_ref().unchecked_ref()));
+        Some(Started { worker, _on_message: on_message, _on_error: on_error })
+    }
+
+    /// Parses `blocks` in the worker, unless it is still busy with other blocks.
+    /// Those are older, so the page parses the blocks it misses by then again.
+    /// `false` when the worker can not parse anything.
+    pub fn parse(&self, blocks: Vec<String>) -> bool {
+        if self.requests.borrow().failed {
+            return false;
+        }
+        let mut started = self.started.borrow_mut();
+        if started.is_none() {
+            *started = self.start();
+        }
+        let Some(Started { worker, .. }) = &*started else {
+            self.requests.borrow_mut().failed = true;
+            return false;
+        };
+        let id = {
+            let mut requests = self.requests.borrow_mut();
+            if requests.sent.is_some() {
+                return true;
+            }
+            requests.next += 1;
+            // requests made before the worker is ready are sent once it is
+            if requests.ready && post(worker, requests.next, &blocks).is_err() {
+                return false;
+            }
+            requests.sent = Some((requests.next, blocks));
+            requests.next
+        };
+        // a worker that never answers is given up on, as it would keep the page from being parsed
+        let (worker, requests, parsed) = (worker.clone(), self.requests.clone(), self.parsed.clone());
+        wasm_bindgen_futures::spawn_local(async move {
+            TimeoutFuture::new(TIMEOUT).await;
+            let waiting = matches!(&requests.borrow().sent, Some((sent, _)) if *sent == id);
+            if waiting {
+                fail(&worker, &requests, &parsed, "no reply in time");
+            }
+        });
+        true
+    }
+}
+
+impl Drop for Parser {
+    fn drop(&mut self) {
+        if let Some(started) = &*self.started.borrow() {
+            started.worker.terminate();
+        }
+    }
+}
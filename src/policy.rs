@@ -0,0 +1,113 @@
+//! Which targets links and images of a page may have, as pages can be imported from anyone.
+
+use serde::{Deserialize, Serialize};
+
+/// Where a link or an image leads, once checked against a `LinkPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target<'s> {
+    /// Page of this wiki, or file next to the app for images.
+    Page(&'s str),
+    /// Opened in a new tab.
+    External(&'s str),
+    /// Opened in place by the browser, like `mailto:` and `tel:`.
+    App(&'s str),
+    /// Run as a script, where the wiki allows it.
+    Script(&'s str),
+    /// Shown, but not followed.
+    Blocked(&'s str),
+}
+
+/// Schemes links may use, set for every wiki in its settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkPolicy {
+    /// Allowed schemes besides `javascript`, in lower case.
+    pub schemes: Vec<String>,
+    /// Whether `javascript:` links run, which lets any page run code.
+    pub scripts: bool,
+}
+
+impl Default for LinkPolicy {
+    fn default() -> Self {
+        LinkPolicy {
+            schemes: ["http", "https", "mailto", "tel"].iter().map(|s| s.to_string()).collect(),
+            scripts: false,
+        }
+    }
+}
+
+/// Opened in a new tab, instead of leaving the wiki.
+const NEW_TAB: [&str; 2] = ["http", "https"];
+
+/// Scheme of `href` in lower case, read the way browsers do,
+/// which ignore leading spaces and any tab or line break.
+pub fn scheme(href: &str) -> Option<String> {
+    let cleaned: String = href
+        .trim_start_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    let (scheme, _) = cleaned.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then(|| scheme.to_ascii_lowercase())
+}
+
+/// Whether `href` leads to a page of this wiki.
+pub fn is_page(href: &str) -> bool {
+    scheme(href).is_none()
+}
+
+impl LinkPolicy {
+    /// Where the link or image `href` leads.
+    pub fn target<'s>(&self, href: &'s str) -> Target<'s> {
+        match scheme(href).as_deref() {
+            None => Target::Page(href),
+            Some("javascript") if self.scripts => Target::Script(href),
+            Some(s) if self.schemes.iter().any(|a| a == s) => {
+                if NEW_TAB.contains(&s) {
+                    Target::External(href)
+                } else {
+                    Target::App(href)
+                }
+            }
+            Some(_) => Target::Blocked(href),
+        }
+    }
+
+    /// Source of an image, which can not be a script.
+    pub fn image<'s>(&self, src: &'s str) -> Target<'s> {
+        match self.target(src) {
+            Target::Script(s) => Target::Blocked(s),
+            t => t,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schemes() {
+        assert_eq!(scheme("https://a.b"), Some("https".into()));
+        assert_eq!(scheme(" Java\tScript:x"), Some("javascript".into()));
+        assert_eq!(scheme("a b/c"), None);
+        assert_eq!(scheme("a/b:c"), None);
+        assert_eq!(scheme(":x"), None);
+        assert_eq!(scheme("page"), None);
+    }
+
+    #[test]
+    fn targets() {
+        let p = LinkPolicy::default();
+        assert_eq!(p.target("Home/x"), Target::Page("Home/x"));
+        assert_eq!(p.target("http://a"), Target::External("http://a"));
+        assert_eq!(p.target("mailto:a@b"), Target::App("mailto:a@b"));
+        assert_eq!(p.target("javascript:alert(1)"), Target::Blocked("javascript:alert(1)"));
+        assert_eq!(p.target("data:text/html,x"), Target::Blocked("data:text/html,x"));
+        let p = LinkPolicy { scripts: true, ..p };
+        assert_eq!(p.target("javascript:x"), Target::Script("javascript:x"));
+        assert_eq!(p.image("javascript:x"), Target::Blocked("javascript:x"));
+    }
+}
@@ -0,0 +1,120 @@
+//! What is stored for each page: its source, with what is known about it.
+
+use creole_nom::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sycamore::prelude::*;
+use wasm_bindgen::JsValue;
+
+use crate::{format_time, storage, tags};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageRecord {
+    /// Creole source of the page.
+    pub text: String,
+    /// Time of the first save in milliseconds since the epoch,
+    /// unknown for pages saved before records were.
+    #[serde(default)]
+    pub created: Option<f64>,
+    /// Time of the latest save, unknown like `created`.
+    #[serde(default)]
+    pub updated: Option<f64>,
+    pub words: usize,
+    /// Tags declared by the page, indexed to list the pages carrying each.
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Words of `text`, leaving out markup standing on its own like list bullets.
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().filter(|w| w.chars().any(char::is_alphanumeric)).count()
+}
+
+impl PageRecord {
+    /// Record of a page stored as bare text, before records were.
+    /// Everything but the times is read from `text`.
+    pub fn legacy(text: &str) -> Self {
+        PageRecord {
+            text: text.to_owned(),
+            created: None,
+            updated: None,
+            words: word_count(text),
+            tags: tags::tags(&creoles(text)),
+        }
+    }
+    /// Record of `text` saved at `now` over the `previous` record of the page, if it had one.
+    pub fn saved(text: &str, now: f64, previous: Option<&PageRecord>) -> Self {
+        PageRecord {
+            created: previous.map_or(Some(now), |p| p.created),
+            updated: Some(now),
+            ..PageRecord::legacy(text)
+        }
+    }
+    /// Record of a page restored from `record`, like one kept in a backup.
+    /// Its times are kept while the rest is read again from its text.
+    pub fn restored(record: &PageRecord) -> Self {
+        PageRecord {
+            created: record.created,
+            updated: record.updated,
+            ..PageRecord::legacy(&record.text)
+        }
+    }
+    /// Reads a record, or the bare text pages were stored as before.
+    pub fn from_value(value: Value) -> Option<Self> {
+        match value {
+            Value::String(text) => Some(PageRecord::legacy(&text)),
+            value => serde_json::from_value(value).ok(),
+        }
+    }
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).expect("could not serialize page record")
+    }
+    pub fn from_js(value: &JsValue) -> Option<Self> {
+        PageRecord::from_value(storage::from_js(value))
+    }
+    pub fn to_js(&self) -> JsValue {
+        storage::to_js(&self.to_value())
+    }
+}
+
+#[derive(Prop)]
+pub struct LastEditedProps {
+    record: PageRecord,
+}
+
+/// When the page was edited, and how long it is.
+#[component]
+pub fn LastEdited<G: Html>(cx: Scope, props: LastEditedProps) -> View<G> {
+    let r = props.record;
+    let mut info = vec![format!("{} words", r.words)];
+    if let Some(t) = r.updated {
+        info.insert(0, format!("Last edited {}", format_time(t)));
+    }
+    if let Some(t) = r.created.filter(|c| Some(*c) != r.updated) {
+        info.push(format!("created {}", format_time(t)));
+    }
+    view! { cx,
+      p(class="last-edited") { (info.join(" · ")) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records() {
+        let old = PageRecord::from_value(Value::from("= Title\n* one two\n\nTags: [[tag/t]]")).unwrap();
+        assert_eq!((old.created, old.updated, old.words), (None, None, 5));
+        assert_eq!(old.tags, ["t"]);
+
+        let first = PageRecord::saved("a b", 1., None);
+        let second = PageRecord::saved("a b c", 2., Some(&first));
+        assert_eq!((second.created, second.updated, second.words), (Some(1.), Some(2.), 3));
+        assert_eq!(PageRecord::from_value(second.to_value()), Some(second.clone()));
+        assert_eq!(PageRecord::saved("a", 3., Some(&old)).created, None);
+
+        let stale = PageRecord { words: 0, tags: vec![], ..second.clone() };
+        assert_eq!(PageRecord::restored(&stale), second);
+    }
+}
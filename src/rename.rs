@@ -0,0 +1,204 @@
+//! Moves a page to another path, keeping the links to it working.
+
+use js_sys::Date;
+use sycamore::{futures::spawn_local_scoped, prelude::*};
+
+use crate::policy::is_page;
+use crate::record::PageRecord;
+use crate::storage::{IdbStore, PageStore, PageWrite};
+use crate::{encode_path, history, write_pages, AppState};
+
+/// First line of a page standing for another one.
+const REDIRECT: &str = "#REDIRECT";
+
+/// Source of a page redirecting to `target`.
+pub fn redirect_stub(target: &str) -> String {
+    format!("{REDIRECT} [[{target}]]")
+}
+
+/// Page that `text` redirects to, from a first line like `#REDIRECT [[target]]`.
+pub fn redirect_target(text: &str) -> Option<&str> {
+    let line = text.lines().next()?.trim();
+    if !line.get(..REDIRECT.len())?.eq_ignore_ascii_case(REDIRECT) {
+        return None;
+    }
+    let (link, _) = line[REDIRECT.len()..].trim_start().strip_prefix("[[")?.split_once("]]")?;
+    let target = link.split('|').next()?.trim();
+    (!target.is_empty() && is_page(target)).then_some(target)
+}
+
+/// `src` with the links to page `old` leading to `new`, or `None` when it has none.
+/// Links keep their text and section, and nothing in `{{{ }}}` or escaped by `~` changes.
+pub fn rewrite_links(src: &str, old: &str, new: &str) -> Option<String> {
+    let mut out = String::with_capacity(src.len());
+    let mut rest = src;
+    let mut changed = false;
+    loop {
+        let link = rest.find("[[");
+        let nowiki = rest.find("{{{");
+        match (link, nowiki) {
+            (Some(l), n) if n.is_none_or(|n| l < n) => {
+                let escaped = rest[..l].ends_with('~');
+                out.push_str(&rest[..l + 2]);
+                rest = &rest[l + 2..];
+                let end = match rest.find("]]") {
+                    Some(end) if !escaped => end,
+                    _ => continue,
+                };
+                let (href, text) = match rest[..end].split_once('|') {
+                    Some((href, text)) => (href, Some(text)),
+                    None => (&rest[..end], None),
+                };
+                let (page, section) = match href.split_once('#') {
+                    Some((page, section)) => (page, Some(section)),
+                    None => (href, None),
+                };
+                if page.trim() != old {
+                    continue;
+                }
+                out.push_str(new);
+                if let Some(section) = section {
+                    out.push('#');
+                    out.push_str(section);
+                }
+                out.push('|');
+                out.push_str(text.unwrap_or_else(|| href.trim()));
+                rest = &rest[end..];
+                changed = true;
+            }
+            (_, Some(n)) => {
+                let end = rest[n + 3..].find("}}}").map_or(rest.len(), |e| n + 6 + e);
+                out.push_str(&rest[..end]);
+                rest = &rest[end..];
+            }
+            _ => break,
+        }
+    }
+    out.push_str(rest);
+    changed.then_some(out)
+}
+
+/// Path typed by the user, without the slashes around it.
+fn clean_path(path: &str) -> &str {
+    path.trim().trim_matches('/')
+}
+
+/// Parts of a path that would end or change the links `rewrite_links` writes to it, or its URL.
+const RESERVED: [&str; 4] = ["|", "#", "?", "]]"];
+
+/// First part of `path` that it can not have, as a page links are rewritten to.
+fn reserved(path: &str) -> Option<&'static str> {
+    RESERVED.into_iter().find(|r| path.contains(r))
+}
+
+/// Moves page `old` to `new` with a copy of its history, and rewrites the links to it in every page.
+/// `old` is left as a redirect to `new` when `redirect` is set, or deleted otherwise.
+/// Everything is written at once, so a failure leaves the wiki as it was.
+pub async fn rename(cx: Scope<'_>, old: &str, new: &str, redirect: bool) -> Result<(), String> {
+    let pages = use_context::<AppState>(cx).pages.get_untracked();
+    if old.is_empty() || old == "help" {
+        return Err(format!("/{old} can not be moved."));
+    }
+    if new.is_empty() || new == "help" || new == old {
+        return Err(format!("Pages can not be moved to /{new}."));
+    }
+    if let Some(r) = reserved(new) {
+        return Err(format!("Page paths can not contain \"{r}\"."));
+    }
+    if pages.contains(new) {
+        return Err(format!("/{new} already exists."));
+    }
+    // every page is read, as the links index may not have every way of writing a link to `old`
+    let records = use_context::<RcSignal<IdbStore>>(cx).get().records().await.map_err(|e| e.to_string())?;
+    let Some((_, record)) = records.iter().find(|(path, _)| path == old) else {
+        return Err(format!("/{old} does not exist."));
+    };
+    let revisions = history::revisions(cx, old).await.map_err(|e| e.to_string())?;
+
+    let text = rewrite_links(&record.text, old, new).unwrap_or_else(|| record.text.clone());
+    // the moved page keeps when it was created
+    let moved = PageRecord::saved(&text, Date::now(), Some(record));
+    let mut pages = vec![
+        PageWrite { record: Some(moved), ..PageWrite::saved(new, &text) },
+        PageWrite::saved(old, &if redirect { redirect_stub(new) } else { String::new() }),
+    ];
+    for (path, r) in records.iter().filter(|(path, _)| path != old) {
+        if let Some(text) = rewrite_links(&r.text, old, new) {
+            pages.push(PageWrite::saved(path, &text));
+        }
+    }
+    let history = revisions.into_iter().map(|r| (new.to_owned(), r)).collect();
+    write_pages(cx, pages, history).await.map_err(|e| e.to_string())
+}
+
+#[derive(Prop)]
+pub struct RenameProps {
+    path: String,
+}
+
+/// Asks where to move a page to.
+#[component]
+pub fn Rename<G: Html>(cx: Scope, props: RenameProps) -> View<G> {
+    let old = create_ref(cx, props.path);
+    let new = create_signal(cx, old.clone());
+    let redirect = create_signal(cx, true);
+    let error = create_signal(cx, String::new());
+    let on_move = move |_| spawn_local_scoped(cx, async move {
+        let target = clean_path(&new.get()).to_owned();
+        match rename(cx, old, &target, *redirect.get()).await {
+            Ok(()) => sycamore_router::navigate(&format!("/w/{}", encode_path(&target))),
+            Err(e) => error.set(e),
+        }
+    });
+
+    view! { cx,
+      div(class="rename") {
+        h2(class="view-name") { (format!("Move /{old}")) }
+        p {
+          label { "New path " input(type="text", bind:value=new) }
+        }
+        p {
+          label { input(type="checkbox", bind:checked=redirect) (format!(" Leave a redirect at /{old}")) }
+          br
+          "Links to the page in every other page are changed to the new path."
+        }
+        button(on:click=on_move) { "Move" }
+        p(class="error") { (error.get().to_string()) }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewrites() {
+        assert_eq!(rewrite_links("see [[a]] and [[a|the a]]", "a", "b/c").as_deref(),
+            Some("see [[b/c|a]] and [[b/c|the a]]"));
+        assert_eq!(rewrite_links("[[ a #intro|x]]", "a", "b").as_deref(), Some("[[b#intro|x]]"));
+        assert_eq!(rewrite_links("[[ab]] [[http://a]]", "a", "b"), None);
+        assert_eq!(rewrite_links("{{{[[a]]}}} ~[[a]] [[a]]", "a", "b").as_deref(),
+            Some("{{{[[a]]}}} ~[[a]] [[b|a]]"));
+        assert_eq!(rewrite_links("[[a", "a", "b"), None);
+    }
+
+    #[test]
+    fn reserves() {
+        assert_eq!(reserved("a/b c"), None);
+        assert_eq!(reserved("a|b"), Some("|"));
+        assert_eq!(reserved("a#b"), Some("#"));
+        assert_eq!(reserved("what?"), Some("?"));
+        assert_eq!(reserved("a]]"), Some("]]"));
+        assert_eq!(reserved("a]b"), None);
+    }
+
+    #[test]
+    fn redirects() {
+        assert_eq!(redirect_target(&redirect_stub("a/b")), Some("a/b"));
+        assert_eq!(redirect_target(" #redirect [[ a | the a ]]\nold text"), Some("a"));
+        assert_eq!(redirect_target("#REDIRECT [[https://a.b]]"), None);
+        assert_eq!(redirect_target("text\n#REDIRECT [[a]]"), None);
+        assert_eq!(redirect_target("#REDIRECT a"), None);
+    }
+}
@@ -8,8 +8,22 @@ pub enum AppRoutes {
     Wiki { path: Vec<String> },
     #[to("/e/<path..>")]
     WikiEdit { path: Vec<String> },
+    #[to("/h/<path..>")]
+    WikiHistory { path: Vec<String> },
+    #[to("/t/<tag..>")]
+    WikiTag { tag: Vec<String> },
+    #[to("/m/<path..>")]
+    WikiMove { path: Vec<String> },
     #[to("/d/<path..>")]
     WikiDelete { path: Vec<String> },
+    #[to("/all")]
+    AllPages,
+    #[to("/backup")]
+    WikiBackup,
+    #[to("/s")]
+    WikiSearch,
+    #[to("/settings")]
+    WikiSettings,
     #[to("/help")]
     Help,
     #[not_found]
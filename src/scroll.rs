@@ -0,0 +1,81 @@
+//! Maps source lines to the top-level blocks of the preview to scroll both panes together.
+
+use creole_nom::prelude::*;
+use sycamore::prelude::*;
+
+use crate::creole_children;
+
+/// Source lines exchanged between the editor and the preview of one page.
+pub struct ScrollSync<'a> {
+    /// Line of the editor caret, followed by the preview.
+    pub caret: &'a Signal<usize>,
+    /// First line shown by the preview, followed by the editor.
+    pub preview: &'a Signal<usize>,
+}
+
+/// Byte offset in `src` of the first source text `i` was parsed from.
+fn first_offset(src: &str, i: &ICreole) -> Option<usize> {
+    let offset = |s: &str| {
+        let o = (s.as_ptr() as usize).checked_sub(src.as_ptr() as usize)?;
+        // leading newlines belong to the line before
+        (o <= src.len()).then(|| o + (s.len() - s.trim_start_matches('\n').len()))
+    };
+    match i {
+        ICreole::Text(t) | ICreole::DontFormat(t) => offset(t),
+        ICreole::Link(h, _) | ICreole::Image(h, _) => offset(h),
+        _ => creole_children(i).iter().filter_map(|c| first_offset(src, c)).min(),
+    }
+}
+
+/// First source line of each top-level item of `tree`, which was parsed from `src`.
+/// Items without any text, like horizontal lines, start where the previous one does.
+pub fn block_lines(src: &str, tree: &[ICreole]) -> Vec<usize> {
+    let mut last = 0;
+    tree.iter()
+        .map(|i| {
+            if let Some(o) = first_offset(src, i) {
+                last = last.max(src[..o].matches('\n').count());
+            }
+            last
+        })
+        .collect()
+}
+
+/// Line of `src` holding the character at `offset`, counted in UTF-16 units like DOM selections.
+pub fn line_at(src: &str, offset: u32) -> usize {
+    let mut units = 0;
+    src.chars()
+        .take_while(|c| {
+            units += c.len_utf16() as u32;
+            units <= offset
+        })
+        .filter(|c| *c == '\n')
+        .count()
+}
+
+/// Index of the block holding `line`.
+pub fn block_at(lines: &[usize], line: usize) -> usize {
+    lines.iter().rposition(|l| *l <= line).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_of_blocks() {
+        let src = "= a\npara\n\n* x\n* y\n----\n|b|";
+        let lines = block_lines(src, &creoles(src));
+        // the horizontal line has no text, so it starts where the list does
+        assert_eq!(lines, [0, 1, 3, 3, 6]);
+        assert_eq!(block_at(&lines, 0), 0);
+        assert_eq!(block_at(&lines, 2), 1);
+        assert_eq!(block_at(&lines, 4), 3);
+        assert_eq!(block_at(&lines, 6), 4);
+        assert_eq!(block_at(&lines, 100), 4);
+        assert_eq!(block_at(&[2, 5], 0), 0);
+        assert_eq!(block_at(&[], 3), 0);
+        assert_eq!(line_at("a\nb\nc", 2), 1);
+        assert_eq!(line_at("é\n한\nc", 4), 2);
+    }
+}
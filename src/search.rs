@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use creole_nom::prelude::*;
+use js_sys::Array;
+use serde_json::{json, Value};
+use sycamore::prelude::*;
+use wasm_bindgen::prelude::*;
+use web_sys::{Event, IdbKeyRange, IdbObjectStore};
+
+use crate::migrations::Schema;
+use crate::record::PageRecord;
+use crate::storage::{self, StorageFailure};
+use crate::{creole_children, encode_path, open_db_transaction, SEARCH_STORE, STORE_NAME};
+
+const TOKEN_INDEX: &str = "token";
+/// Characters kept around the first match of a snippet.
+const SNIPPET_BEFORE: usize = 40;
+const SNIPPET_LEN: usize = 160;
+
+/// Words of `text` with their byte offsets.
+fn words(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(move |w| (w.as_ptr() as usize - text.as_ptr() as usize, w))
+}
+
+/// Lowercased words of `text`, sorted and without duplicates.
+pub fn tokens(text: &str) -> Vec<String> {
+    let mut rst: Vec<String> = words(text).map(|(_, w)| w.to_lowercase()).collect();
+    rst.sort();
+    rst.dedup();
+    rst
+}
+
+/// Every `ICreole::Text` of `tree`.
+fn texts<'a>(tree: &[ICreole<'a>]) -> Vec<&'a str> {
+    fn walk<'a>(tree: &[ICreole<'a>], rst: &mut Vec<&'a str>) {
+        for i in tree {
+            match i {
+                ICreole::Text(t) => rst.push(t),
+                _ => walk(creole_children(i), rst),
+            }
+        }
+    }
+    let mut rst = vec![];
+    walk(tree, &mut rst);
+    rst
+}
+
+fn matches(word: &str, query: &[String]) -> bool {
+    let word = word.to_lowercase();
+    query.iter().any(|q| word.starts_with(q.as_str()))
+}
+
+/// Part of `text` around its first match, split into plain and matching pieces.
+pub fn snippet<'a>(text: &'a str, query: &[String]) -> Option<Vec<(&'a str, bool)>> {
+    let (first, _) = words(text).find(|(_, w)| matches(w, query))?;
+    let start = text[..first].char_indices().rev().nth(SNIPPET_BEFORE - 1).map_or(0, |(i, _)| i);
+    let end = text[start..].char_indices().nth(SNIPPET_LEN).map_or(text.len(), |(i, _)| start + i);
+    let part = &text[start..end];
+    let mut rst = vec![];
+    let mut last = 0;
+    for (i, w) in words(part).filter(|(_, w)| matches(w, query)) {
+        rst.push((&part[last..i], false));
+        rst.push((w, true));
+        last = i + w.len();
+    }
+    rst.push((&part[last..], false));
+    Some(rst)
+}
+
+/// Creates the `search` store. Each record lists the tokens of one page as
+/// `{ tokens: [...] }` and the multi-entry `token` index inverts it.
+pub fn create_store(db: &mut dyn Schema) -> storage::Result<()> {
+    db.create_store(SEARCH_STORE)?;
+    db.create_index(SEARCH_STORE, TOKEN_INDEX, "tokens", true)
+}
+
+/// Record of the tokens of `path` and its `text`, or `None` for a deleted page.
+pub fn record(path: &str, text: &str) -> Option<Value> {
+    if text.is_empty() {
+        return None;
+    }
+    let content = texts(&creoles(text)).join(" ");
+    Some(json!({ "tokens": tokens(&format!("{path} {content}")) }))
+}
+
+/// Replaces the tokens recorded for `path` by the ones of its name and `text`.
+pub fn update(store: &IdbObjectStore, path: &str, text: &str) -> storage::Result<()> {
+    match record(path, text) {
+        Some(r) => store.put_with_key(&storage::to_js(&r), &path.into())?,
+        None => store.delete(&path.into())?,
+    };
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+pub struct Hit {
+    pub path: String,
+    pub text: String,
+    /// Query tokens found in the page.
+    pub matched: usize,
+    /// Words of the page matching any query token.
+    pub count: usize,
+}
+
+/// Pages matching any token of `query` as a prefix, best matches first.
+pub async fn search(cx: Scope<'_>, query: &[String]) -> storage::Result<Vec<Hit>> {
+    let index = open_db_transaction(cx, &[SEARCH_STORE], false)?.object_store(SEARCH_STORE)?.index(TOKEN_INDEX)?;
+    let requests = query.iter().map(|q| {
+        let range = IdbKeyRange::bound(&q.into(), &format!("{q}\u{ffff}").into())?;
+        Ok(index.get_all_keys_with_key(&range)?)
+    }).collect::<storage::Result<Vec<_>>>()?;
+
+    let mut matched: HashMap<String, usize> = HashMap::new();
+    for r in requests {
+        let mut keys: Vec<String> = storage::request(&r).await?
+          .unchecked_into::<Array>().iter().filter_map(|k| k.as_string()).collect();
+        keys.sort();
+        keys.dedup();
+        for k in keys {
+            *matched.entry(k).or_default() += 1;
+        }
+    }
+
+    let store = open_db_transaction(cx, &[STORE_NAME], false)?.object_store(STORE_NAME)?;
+    let requests = matched.into_iter().map(|(path, m)| {
+        let r = store.get(&path.clone().into())?;
+        Ok((path, m, r))
+    }).collect::<storage::Result<Vec<_>>>()?;
+    let mut hits = vec![];
+    for (path, m, r) in requests {
+        let text = PageRecord::from_js(&storage::request(&r).await?).map(|r| r.text).unwrap_or_default();
+        let count = words(&text).filter(|(_, w)| matches(w, query)).count();
+        hits.push(Hit { path, text, matched: m, count });
+    }
+    hits.sort_by(|a, b| b.matched.cmp(&a.matched).then(b.count.cmp(&a.count)).then(a.path.cmp(&b.path)));
+    Ok(hits)
+}
+
+#[derive(Prop)]
+pub struct SearchProps {
+    query: String,
+}
+
+#[component]
+pub async fn Search<G: Html>(cx: Scope<'_>, props: SearchProps) -> View<G> {
+    let query = create_ref(cx, tokens(&props.query));
+    let hits = if query.is_empty() {
+        vec![]
+    } else {
+        match search(cx, query).await {
+            Ok(hits) => hits,
+            Err(error) => return view! { cx, StorageFailure { error: error } },
+        }
+    };
+    let title = format!("{} results for \"{}\"", hits.len(), props.query);
+
+    let items = View::new_fragment(hits.into_iter().map(|hit| {
+        let path = format!("/w/{}", encode_path(&hit.path));
+        let href = path.clone();
+        let on_click = move |e: Event| {
+            e.prevent_default();
+            sycamore_router::navigate(&path);
+        };
+        let name = if hit.path.is_empty() { String::from("(home)") } else { hit.path.clone() };
+        let snippet = texts(&creoles(&hit.text)).into_iter()
+          .find_map(|t| snippet(t, query))
+          .map(|parts| View::new_fragment(parts.into_iter().map(|(s, m)| {
+              let s = s.to_string();
+              if m {
+                  view! { cx, mark { (s) } }
+              } else {
+                  view! { cx, (s) }
+              }
+          }).collect()))
+          .unwrap_or_else(View::empty);
+        view! { cx,
+          li {
+            a(href=href, on:click=on_click) { (name) }
+            p(class="snippet") { (snippet) }
+          }
+        }
+    }).collect());
+
+    view! { cx,
+      div(class="search") {
+        h2(class="view-name") { (title) }
+        ol { (items) }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_and_prefixes() {
+        assert_eq!(tokens("Hello, hello WORLD-wide Été"), ["hello", "wide", "world", "été"]);
+        let query = tokens("SEA été");
+        assert!(matches("Searching", &query));
+        assert!(matches("ÉTÉS", &query));
+        assert!(!matches("research", &query));
+    }
+
+    #[test]
+    fn snippets() {
+        let text = format!("{} target {}", "가".repeat(100), "나".repeat(300));
+        let parts = snippet(&text, &tokens("tar")).unwrap();
+        assert_eq!(parts[0], (format!("{} ", "가".repeat(SNIPPET_BEFORE - 1)).as_str(), false));
+        assert_eq!(parts[1], ("target", true));
+        assert_eq!(parts.iter().map(|(s, _)| s.chars().count()).sum::<usize>(), SNIPPET_LEN);
+
+        assert_eq!(snippet("a short text", &tokens("short")).unwrap(), [("a ", false), ("short", true), (" text", false)]);
+        assert_eq!(snippet("nothing here", &tokens("else")), None);
+    }
+}
@@ -0,0 +1,71 @@
+//! Preferences of the wiki, kept in the settings store next to its pages.
+
+use sycamore::prelude::*;
+use wasm_bindgen::JsValue;
+
+use crate::policy::LinkPolicy;
+use crate::{open_db_transaction, storage, AppState, SETTINGS_STORE};
+
+/// Name of the chosen `Theme`.
+pub const THEME: &str = "theme";
+/// `LinkPolicy` of the wiki as JSON.
+pub const LINK_POLICY: &str = "link-policy";
+
+/// The setting under `key`, or `undefined` when it was never set.
+pub async fn get(cx: Scope<'_>, key: &str) -> storage::Result<JsValue> {
+    let store = open_db_transaction(cx, &[SETTINGS_STORE], false)?.object_store(SETTINGS_STORE)?;
+    storage::request(&store.get(&key.into())?).await
+}
+
+pub fn set(cx: Scope, key: &str, value: &JsValue) -> storage::Result<()> {
+    let store = open_db_transaction(cx, &[SETTINGS_STORE], true)?.object_store(SETTINGS_STORE)?;
+    store.put_with_key(value, &key.into())?;
+    Ok(())
+}
+
+/// Edits the settings of the wiki that are not switched from the nav.
+#[component]
+pub fn Settings<G: Html>(cx: Scope) -> View<G> {
+    let state = use_context::<AppState>(cx);
+    let policy = state.link_policy.get();
+    let schemes = create_signal(cx, policy.schemes.join(", "));
+    let scripts = create_signal(cx, policy.scripts);
+    let status = create_signal(cx, String::new());
+    let save = move |_| {
+        let policy = LinkPolicy {
+            schemes: schemes.get()
+                .split(',')
+                .map(|s| s.trim().to_ascii_lowercase())
+                .filter(|s| !s.is_empty() && s != "javascript")
+                .collect(),
+            scripts: *scripts.get(),
+        };
+        let json = serde_json::to_string(&policy).expect("could not serialize policy");
+        if let Err(e) = set(cx, LINK_POLICY, &json.into()) {
+            status.set(e.to_string());
+            return;
+        }
+        schemes.set(policy.schemes.join(", "));
+        state.link_policy.set(policy);
+        status.set(String::from("Saved"));
+    };
+
+    view! { cx,
+      div(class="settings") {
+        h2 { "Settings" }
+        h3 { "Links" }
+        p {
+          label { "Allowed schemes " input(type="text", bind:value=schemes) }
+          br
+          "Links and images using any other scheme are shown, but can not be followed."
+        }
+        p {
+          label { input(type="checkbox", bind:checked=scripts) " Run javascript: links" }
+          br
+          "Only for wikis where every page is trusted, as their links can then run any code."
+        }
+        button(on:click=save) { "Save" }
+        (status.get())
+      }
+    }
+}
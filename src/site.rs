@@ -0,0 +1,72 @@
+//! Static HTML export of every page.
+
+use std::collections::BTreeMap;
+
+use creole_nom::prelude::*;
+use sycamore::prelude::*;
+
+use crate::html::{escape, file_href, page_file, relative_href, root, root_of, tag_file, to_html, Links};
+use crate::policy::LinkPolicy;
+use crate::zip::Zip;
+use crate::record::PageRecord;
+use crate::storage;
+use crate::{open_db_transaction, AppState, STORE_NAME};
+
+const STYLE: &str = "figure { display: inline-block; }
+table { border-collapse: collapse; }
+thead { background: #eee; }
+td { padding: .25rem; border: 1px solid; word-break: keep-all; }
+pre { display: inline; }
+body > pre { display: block; }
+ol, ul { padding-inline-start: 1em; }
+img { max-width: 100%; }
+.blocked-link { text-decoration: line-through; color: #999; }
+";
+
+fn document(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!doctype html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"{root}style.css\">\n</head>\n<body>\n{body}\n</body>\n</html>\n",
+        escape(title)
+    )
+}
+
+/// A complete HTML document for the page at `path`.
+pub fn page(path: &str, text: &str, policy: &LinkPolicy) -> String {
+    let tag = |tag: &str| file_href(&root(path), &tag_file(tag));
+    let body = to_html(&creoles(text), &Links { policy, link: &|href| relative_href(path, href), tag: &tag });
+    document(if path.is_empty() { "Home" } else { path }, &root(path), &body)
+}
+
+/// A complete HTML document listing the pages at `paths`, which carry `tag`.
+pub fn tag_page(tag: &str, paths: &[String]) -> String {
+    let root = root_of(&tag_file(tag));
+    let items: String = paths.iter()
+        .map(|p| {
+            let name = if p.is_empty() { "(home)" } else { p };
+            format!("<li><a href=\"{}\">{}</a></li>", escape(&file_href(&root, &page_file(p))), escape(name))
+        })
+        .collect();
+    let title = format!("Pages tagged {tag}");
+    document(&title, &root, &format!("<h2>{}</h2><ul>{items}</ul>", escape(&title)))
+}
+
+/// Every page rendered to its own HTML file, zipped together with a stylesheet and a file for every tag.
+pub async fn export(cx: Scope<'_>) -> storage::Result<Vec<u8>> {
+    let store = open_db_transaction(cx, &[STORE_NAME], false)?.object_store(STORE_NAME)?;
+    let policy = use_context::<AppState>(cx).link_policy.get();
+    let mut zip = Zip::new();
+    zip.add("style.css", STYLE.as_bytes());
+    let mut tagged = BTreeMap::<String, Vec<String>>::new();
+    for (k, v) in storage::entries(&store).await? {
+        if let (Some(path), Some(record)) = (k.as_string(), PageRecord::from_js(&v)) {
+            zip.add(&page_file(&path), page(&path, &record.text, &policy).as_bytes());
+            for tag in record.tags {
+                tagged.entry(tag).or_default().push(path.clone());
+            }
+        }
+    }
+    for (tag, paths) in &tagged {
+        zip.add(&tag_file(tag), tag_page(tag, paths).as_bytes());
+    }
+    Ok(zip.finish())
+}
@@ -0,0 +1,421 @@
+//! Pages in IndexedDB, with every request awaited and its failure reported.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::future::Future;
+use std::rc::Rc;
+
+use js_sys::{Array, Date, Promise};
+use serde_json::Value;
+use sycamore::prelude::*;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    DomException, Event, EventTarget, IdbCursorWithValue, IdbDatabase, IdbIndexParameters, IdbObjectStore, IdbRequest,
+    IdbTransaction, IdbTransactionMode, IdbVersionChangeEvent,
+};
+
+use crate::history::Revision;
+use crate::migrations::{Derive, Schema};
+use crate::record::PageRecord;
+use crate::{history, links, search, AppState, HISTORY_STORE, LINKS_STORE, SEARCH_STORE, STORE_NAME};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageError {
+    /// The browser has no IndexedDB, or disables it like some private windows do.
+    Unavailable,
+    /// Another tab keeps the wiki open with an older version of the app.
+    Blocked,
+    /// Converting the wiki to the current version failed, which left it as it was.
+    Upgrade(String),
+    /// A request or transaction failed, with the reason given by the browser.
+    Failed(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StorageError::Unavailable => write!(f, "This browser does not let the wiki store pages. Private windows often do not."),
+            StorageError::Blocked => write!(f, "Another tab has the wiki open with an older version. Close it, then reload."),
+            StorageError::Upgrade(e) => write!(f, "The wiki could not be upgraded to this version, and was left as it was: {e}"),
+            StorageError::Failed(e) => write!(f, "The wiki could not be read or written: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<JsValue> for StorageError {
+    fn from(e: JsValue) -> Self {
+        match e.dyn_ref::<DomException>() {
+            Some(e) => StorageError::Failed(format!("{} ({})", e.message(), e.name())),
+            None => StorageError::Failed(e.as_string().unwrap_or_else(|| format!("{e:?}"))),
+        }
+    }
+}
+
+impl From<Option<DomException>> for StorageError {
+    fn from(e: Option<DomException>) -> Self {
+        e.map_or_else(|| StorageError::Failed(String::from("unknown error")), |e| JsValue::from(e).into())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, StorageError>;
+
+/// Event name with the closure listening to it.
+type Listener = (&'static str, Closure<dyn FnMut(Event)>);
+
+/// Listeners removed again once dropped, as the closures they call are.
+struct Listeners {
+    target: EventTarget,
+    closures: Vec<Listener>,
+}
+
+impl Drop for Listeners {
+    fn drop(&mut self) {
+        for (name, c) in &self.closures {
+            self.target.remove_event_listener_with_callback(name, c.as_ref().unchecked_ref()).unwrap_or(());
+        }
+    }
+}
+
+/// Index in `events` of the first one fired by `target`.
+async fn first_event(target: &EventTarget, events: &[&'static str]) -> usize {
+    let mut listeners = Listeners { target: target.clone(), closures: vec![] };
+    let promise = Promise::new(&mut |resolve: js_sys::Function, _reject: js_sys::Function| {
+        for (i, &name) in events.iter().enumerate() {
+            let resolve = resolve.clone();
+            let c = Closure::<dyn FnMut(Event)>::new(move |_: Event| {
+                resolve.call1(&JsValue::NULL, &(i as u32).into()).unwrap_or_default();
+            });
+            target.add_event_listener_with_callback(name, c.as_ref().unchecked_ref()).unwrap_or(());
+            listeners.closures.push((name, c));
+        }
+    });
+    let fired = JsFuture::from(promise).await.ok().and_then(|i| i.as_f64());
+    fired.map_or(events.len(), |i| i as usize)
+}
+
+/// Result of `r`, once it succeeded.
+pub async fn request(r: &IdbRequest) -> Result<JsValue> {
+    match first_event(r, &["success", "error"]).await {
+        0 => Ok(r.result()?),
+        _ => Err(r.error()?.into()),
+    }
+}
+
+/// Waits for every request of `t` to be written.
+pub async fn complete(t: &IdbTransaction) -> Result<()> {
+    match first_event(t, &["complete", "error", "abort"]).await {
+        0 => Ok(()),
+        _ => Err(t.error().into()),
+    }
+}
+
+/// Opens DB `name` at `version`, upgrading it with `upgrade` from the version it had before.
+/// An error of `upgrade` aborts the upgrade, so the DB stays as it was.
+pub async fn open(
+    name: &str,
+    version: u32,
+    upgrade: impl FnOnce(&IdbDatabase, &IdbTransaction, u32) -> Result<()> + 'static,
+) -> Result<IdbDatabase> {
+    let factory = web_sys::window()
+        .and_then(|w| w.indexed_db().ok().flatten())
+        .ok_or(StorageError::Unavailable)?;
+    let req = factory.open_with_u32(name, version)?;
+    let failed = Rc::new(RefCell::new(None));
+    let on_upgrade = {
+        let (req, failed) = (req.clone(), failed.clone());
+        Closure::once(move |e: IdbVersionChangeEvent| {
+            let (Ok(db), Some(t)) = (req.result(), req.transaction()) else {
+                return;
+            };
+            if let Err(err) = upgrade(db.unchecked_ref(), &t, e.old_version() as u32) {
+                let reason = match err {
+                    StorageError::Failed(reason) | StorageError::Upgrade(reason) => reason,
+                    e => e.to_string(),
+                };
+                failed.replace(Some(StorageError::Upgrade(reason)));
+                t.abort().unwrap_or(());
+            }
+        })
+    };
+    req.set_onupgradeneeded(Some(on_upgrade.as_ref().unchecked_ref()));
+    let fired = first_event(&req, &["success", "error", "blocked"]).await;
+    req.set_onupgradeneeded(None);
+    match fired {
+        0 => Ok(req.result()?.unchecked_into()),
+        2 => Err(StorageError::Blocked),
+        _ => Err(match failed.take() {
+            Some(e) => e,
+            None => req.error()?.into(),
+        }),
+    }
+}
+
+pub fn to_js(value: &Value) -> JsValue {
+    js_sys::JSON::parse(&value.to_string()).unwrap_or_default()
+}
+
+pub fn from_js(value: &JsValue) -> Value {
+    js_sys::JSON::stringify(value).ok()
+        .and_then(|s| s.as_string())
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// The DB while it is upgraded, which migrations change.
+pub struct Upgrade<'u> {
+    pub db: &'u IdbDatabase,
+    pub transaction: &'u IdbTransaction,
+}
+
+impl Schema for Upgrade<'_> {
+    fn create_store(&mut self, name: &str) -> Result<()> {
+        self.db.create_object_store(name)?;
+        Ok(())
+    }
+    fn create_index(&mut self, store: &str, name: &str, key_path: &str, multi_entry: bool) -> Result<()> {
+        let params = IdbIndexParameters::new();
+        params.set_multi_entry(multi_entry);
+        self.transaction.object_store(store)?
+            .create_index_with_str_and_optional_parameters(name, key_path, &params)?;
+        Ok(())
+    }
+    fn put(&mut self, store: &str, key: &str, value: Value) -> Result<()> {
+        self.transaction.object_store(store)?.put_with_key(&to_js(&value), &key.into())?;
+        Ok(())
+    }
+    /// Goes through `store` with a cursor, as awaiting would end the upgrade transaction.
+    fn for_each(&mut self, store: &str, mut f: Derive) -> Result<()> {
+        let r = self.transaction.object_store(store)?.open_cursor()?;
+        let transaction = self.transaction.clone();
+        let cb = Closure::<dyn FnMut(Event)>::new(move |e: Event| {
+            let Some(c) = e.target().and_then(|t| t.unchecked_into::<IdbRequest>().result().ok()) else {
+                return;
+            };
+            let Some(c) = c.dyn_ref::<IdbCursorWithValue>() else {
+                return;
+            };
+            if let Some(key) = c.key().ok().and_then(|k| k.as_string()) {
+                for (store, key, value) in f(&key, &from_js(&c.value().unwrap_or_default())) {
+                    let put = transaction.object_store(store)
+                        .and_then(|s| s.put_with_key(&to_js(&value), &JsValue::from_str(&key)));
+                    if let Err(e) = put {
+                        log::error!("could not migrate {} : {:?}", key, e);
+                        transaction.abort().unwrap_or(());
+                        return;
+                    }
+                }
+            }
+            c.continue_().unwrap_or(());
+        });
+        r.set_onsuccess(Some(cb.as_ref().unchecked_ref()));
+        cb.forget();
+        Ok(())
+    }
+}
+
+/// Keys and values of every record in `store`, read through a cursor.
+pub async fn entries(store: &IdbObjectStore) -> Result<Vec<(JsValue, JsValue)>> {
+    let r = store.open_cursor()?;
+    let mut entries = vec![];
+    loop {
+        match request(&r).await?.dyn_into::<IdbCursorWithValue>() {
+            Ok(c) => {
+                entries.push((c.key()?, c.value()?));
+                c.continue_()?;
+            }
+            Err(_) => return Ok(entries),
+        }
+    }
+}
+
+/// Stores written along with a page.
+pub const PAGE_STORES: [&str; 4] = [STORE_NAME, HISTORY_STORE, LINKS_STORE, SEARCH_STORE];
+
+/// A page to write with everything derived from it.
+#[derive(Debug, Clone)]
+pub struct PageWrite {
+    pub path: String,
+    /// Source of the page, or nothing to delete it.
+    pub text: String,
+    /// Record stored as it is, instead of the one of `text` saved now over the page at `path`.
+    pub record: Option<PageRecord>,
+    /// Whether `text` is appended to the history of the page as a new revision.
+    pub revision: bool,
+    /// Whether that revision replaces the latest one, when the page was saved less than
+    /// `history::MERGE_WINDOW` before. Only for pages written without a `record`.
+    pub merge: bool,
+}
+
+impl PageWrite {
+    /// `text` saved now at `path` as a revision of its own.
+    pub fn saved(path: &str, text: &str) -> Self {
+        PageWrite { path: path.to_owned(), text: text.to_owned(), record: None, revision: true, merge: false }
+    }
+    /// `text` typed at `path` in the editor, merged with what was typed just before.
+    pub fn typed(path: &str, text: &str) -> Self {
+        PageWrite { merge: true, ..PageWrite::saved(path, text) }
+    }
+}
+
+/// Adds the requests writing `page` to `t`, a transaction over `PAGE_STORES`.
+fn write_page(t: &IdbTransaction, page: &PageWrite) -> Result<()> {
+    let (path, text, now) = (page.path.as_str(), page.text.as_str(), Date::now());
+    let store = t.object_store(STORE_NAME)?;
+    let revisions = t.object_store(HISTORY_STORE)?;
+    match &page.record {
+        _ if text.is_empty() => {
+            store.delete(&path.into())?;
+        }
+        Some(record) => {
+            store.put_with_key(&record.to_js(), &path.into())?;
+        }
+        None => {
+            // the record keeps when the page was created, so it is read in the same transaction
+            let previous = store.get(&path.into())?;
+            let (r, revisions, path, text, merge) = (previous.clone(), revisions.clone(), path.to_owned(), text.to_owned(), page.merge);
+            let on_previous = Closure::once_into_js(move || {
+                let previous = r.result().ok().and_then(|v| PageRecord::from_js(&v));
+                let record = PageRecord::saved(&text, now, previous.as_ref());
+                // the revision saved last is the one keyed by the time of the record
+                let replaced = previous.and_then(|p| history::merged(p.updated, now)).filter(|_| merge);
+                let written = store.put_with_key(&record.to_js(), &JsValue::from_str(&path)).map_err(StorageError::from)
+                    .and_then(|_| replaced.map_or(Ok(()), |time| history::delete(&revisions, &path, time)));
+                if let (Err(_), Some(t)) = (written, r.transaction()) {
+                    t.abort().unwrap_or(());
+                }
+            });
+            previous.set_onsuccess(Some(on_previous.unchecked_ref()));
+        }
+    }
+    if page.revision {
+        history::put(&revisions, path, now, text)?;
+    }
+    links::update(&t.object_store(LINKS_STORE)?, path, text)?;
+    search::update(&t.object_store(SEARCH_STORE)?, path, text)
+}
+
+/// Where pages are kept, with what is derived from them.
+pub trait PageStore {
+    /// Record of the page at `path`, or `None` when there is no such page.
+    async fn get(&self, path: &str) -> Result<Option<PageRecord>>;
+    /// Stores `text` at `path` as its latest revision, with its links and search tokens.
+    /// The write starts right away, so it is not lost when the future is dropped.
+    fn put(&self, path: &str, text: &str) -> impl Future<Output = Result<()>> + 'static;
+    /// Removes the page at `path`, keeping its history with a revision marking the deletion.
+    fn delete(&self, path: &str) -> impl Future<Output = Result<()>> + 'static;
+    /// Writes `pages`, and the revisions of `history` at their paths, all in one transaction
+    /// so that a failure writes none of them. Starts right away like `put`.
+    fn write_all(&self, pages: Vec<PageWrite>, history: Vec<(String, Revision)>) -> impl Future<Output = Result<()>> + 'static;
+    /// Paths of every page.
+    async fn list(&self) -> Result<Vec<String>>;
+    /// Path and record of every page.
+    async fn records(&self) -> Result<Vec<(String, PageRecord)>>;
+    fn transaction(&self, stores: &[&str], write: bool) -> Result<IdbTransaction>;
+}
+
+/// The wiki kept in IndexedDB.
+#[derive(Debug, Clone)]
+pub struct IdbStore {
+    db: IdbDatabase,
+}
+
+impl IdbStore {
+    pub fn new(db: IdbDatabase) -> Self {
+        IdbStore { db }
+    }
+    pub fn name(&self) -> String {
+        self.db.name()
+    }
+}
+
+impl PageStore for IdbStore {
+    async fn get(&self, path: &str) -> Result<Option<PageRecord>> {
+        let store = self.transaction(&[STORE_NAME], false)?.object_store(STORE_NAME)?;
+        Ok(PageRecord::from_js(&request(&store.get(&path.into())?).await?))
+    }
+    fn put(&self, path: &str, text: &str) -> impl Future<Output = Result<()>> + 'static {
+        self.write_all(vec![PageWrite::saved(path, text)], vec![])
+    }
+    fn delete(&self, path: &str) -> impl Future<Output = Result<()>> + 'static {
+        self.write_all(vec![PageWrite::saved(path, "")], vec![])
+    }
+    fn write_all(&self, pages: Vec<PageWrite>, history: Vec<(String, Revision)>) -> impl Future<Output = Result<()>> + 'static {
+        let written = self.transaction(&PAGE_STORES, true).and_then(|t| {
+            let requested = (|| {
+                let revisions = t.object_store(HISTORY_STORE)?;
+                for (path, r) in &history {
+                    history::put(&revisions, path, r.time, &r.text)?;
+                }
+                pages.iter().try_for_each(|p| write_page(&t, p))
+            })();
+            if requested.is_err() {
+                t.abort().unwrap_or(());
+            }
+            requested.map(|()| t)
+        });
+        async move { complete(&written?).await }
+    }
+    async fn list(&self) -> Result<Vec<String>> {
+        let store = self.transaction(&[STORE_NAME], false)?.object_store(STORE_NAME)?;
+        let keys: Array = request(&store.get_all_keys()?).await?.unchecked_into();
+        Ok(keys.iter().filter_map(|k| k.as_string()).collect())
+    }
+    async fn records(&self) -> Result<Vec<(String, PageRecord)>> {
+        let store = self.transaction(&[STORE_NAME], false)?.object_store(STORE_NAME)?;
+        Ok(entries(&store).await?.into_iter()
+            .filter_map(|(k, v)| Some((k.as_string()?, PageRecord::from_js(&v)?)))
+            .collect())
+    }
+    fn transaction(&self, stores: &[&str], write: bool) -> Result<IdbTransaction> {
+        let names = stores.iter().map(|s| JsValue::from_str(s)).collect::<Array>();
+        let mode = if write { IdbTransactionMode::Readwrite } else { IdbTransactionMode::Readonly };
+        Ok(self.db.transaction_with_str_sequence_and_mode(&names, mode)?)
+    }
+}
+
+#[derive(Prop)]
+pub struct StorageFailureProps {
+    error: StorageError,
+}
+
+/// Tells why the wiki could not be read, in place of what needed it.
+#[component]
+pub fn StorageFailure<G: Html>(cx: Scope, props: StorageFailureProps) -> View<G> {
+    log::error!("{}", props.error);
+    let reload = |_| {
+        if let Some(w) = web_sys::window() {
+            w.location().reload().unwrap_or(());
+        }
+    };
+    view! { cx,
+      div(class="storage-error") {
+        h2 { "Storage error" }
+        p { (props.error.to_string()) }
+        button(on:click=reload) { "Reload" }
+      }
+    }
+}
+
+/// Tells that a page could not be saved, until dismissed.
+#[component]
+pub fn SaveFailure<G: Html>(cx: Scope) -> View<G> {
+    let failure = &use_context::<AppState>(cx).save_failure;
+    view! { cx,
+      ( match failure.get().as_ref() {
+          Some((path, error)) => {
+            let message = format!("/{path} could not be saved. {error}");
+            view! { cx,
+              div(class="storage-error") {
+                (message)
+                button(on:click=|_| failure.set(None)) { "Dismiss" }
+              }
+            }
+          }
+          None => view! { cx, },
+        }
+      )
+    }
+}
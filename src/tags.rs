@@ -0,0 +1,154 @@
+//! Tags declared by pages on a line like `Tags: [[tag/foo]], [[tag/bar]]`.
+
+use std::collections::BTreeMap;
+
+use creole_nom::prelude::*;
+use js_sys::Array;
+use sycamore::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Event, IdbCursor};
+
+use crate::migrations::Schema;
+use crate::storage::{self, StorageFailure};
+use crate::{encode_path, open_db_transaction, STORE_NAME};
+
+/// Index of the page records by each of their tags.
+const TAG_INDEX: &str = "tag";
+/// Pages linked from a tags line stand for the tags.
+const TAG_PREFIX: &str = "tag/";
+const TAGS_LINE: &str = "Tags:";
+
+/// Name of the tag a link to `href` stands for.
+pub fn tag_name(href: &str) -> Option<&str> {
+    href.strip_prefix(TAG_PREFIX).map(str::trim).filter(|t| !t.is_empty())
+}
+
+/// Tags declared in `tree`, sorted and without duplicates.
+pub fn tags(tree: &[ICreole]) -> Vec<String> {
+    let mut rst = vec![];
+    for i in tree {
+        let ICreole::Line(items) = i else {
+            continue;
+        };
+        if !matches!(items.first(), Some(ICreole::Text(t)) if t.trim_start().starts_with(TAGS_LINE)) {
+            continue;
+        }
+        for i in items {
+            if let ICreole::Link(href, _) = i {
+                rst.extend(tag_name(href).map(str::to_owned));
+            }
+        }
+    }
+    rst.sort();
+    rst.dedup();
+    rst
+}
+
+/// Indexes the page records by their `tags`.
+pub fn create_index(db: &mut dyn Schema) -> storage::Result<()> {
+    db.create_index(STORE_NAME, TAG_INDEX, "tags", true)
+}
+
+/// Pages carrying `tag`.
+pub async fn tagged(cx: Scope<'_>, tag: &str) -> storage::Result<Vec<String>> {
+    let r = open_db_transaction(cx, &[STORE_NAME], false)?
+      .object_store(STORE_NAME)?
+      .index(TAG_INDEX)?
+      .get_all_keys_with_key(&tag.into())?;
+    let keys: Array = storage::request(&r).await?.unchecked_into();
+    Ok(keys.iter().filter_map(|k| k.as_string()).collect())
+}
+
+/// Every tag with the number of pages carrying it.
+pub async fn counts(cx: Scope<'_>) -> storage::Result<BTreeMap<String, usize>> {
+    let r = open_db_transaction(cx, &[STORE_NAME], false)?
+      .object_store(STORE_NAME)?
+      .index(TAG_INDEX)?
+      .open_key_cursor()?;
+    let mut counts = BTreeMap::new();
+    while let Ok(c) = storage::request(&r).await?.dyn_into::<IdbCursor>() {
+        if let Some(tag) = c.key()?.as_string() {
+            *counts.entry(tag).or_default() += 1;
+        }
+        c.continue_()?;
+    }
+    Ok(counts)
+}
+
+fn tag_link<G: Html>(cx: Scope, tag: String) -> View<G> {
+    let path = format!("/t/{}", encode_path(&tag));
+    let href = path.clone();
+    let on_click = move |e: Event| {
+        e.prevent_default();
+        sycamore_router::navigate(&path);
+    };
+    view! { cx, a(href=href, on:click=on_click) { (tag) } }
+}
+
+/// Every tag, sized by how many pages carry it.
+#[component]
+pub async fn TagCloud<G: Html>(cx: Scope<'_>) -> View<G> {
+    let counts = match counts(cx).await {
+        Ok(counts) => counts,
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    };
+    let Some(&most) = counts.values().max() else {
+        return view! { cx, };
+    };
+    let tags = View::new_fragment(counts.into_iter().map(|(tag, n)| {
+        let size = 1. + n as f64 / most as f64;
+        let link = tag_link(cx, tag);
+        view! { cx,
+          span(style=format!("font-size: {size:.2}em")) { (link) } " "
+        }
+    }).collect());
+    view! { cx,
+      div(class="tag-cloud") {
+        h3 { "Tags" }
+        (tags)
+      }
+    }
+}
+
+#[derive(Prop)]
+pub struct TaggedProps {
+    tag: String,
+}
+
+/// Pages carrying a tag.
+#[component]
+pub async fn Tagged<G: Html>(cx: Scope<'_>, props: TaggedProps) -> View<G> {
+    let pages = match tagged(cx, &props.tag).await {
+        Ok(pages) => pages,
+        Err(error) => return view! { cx, StorageFailure { error: error } },
+    };
+    let items = View::new_fragment(pages.into_iter().map(|p| {
+        let path = format!("/w/{}", encode_path(&p));
+        let href = path.clone();
+        let on_click = move |e: Event| {
+            e.prevent_default();
+            sycamore_router::navigate(&path);
+        };
+        let name = if p.is_empty() { String::from("(home)") } else { p };
+        view! { cx, li { a(href=href, on:click=on_click) { (name) } } }
+    }).collect());
+    view! { cx,
+      div(class="tagged") {
+        h2(class="view-name") { (format!("Pages tagged {}", props.tag)) }
+        ul { (items) }
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn declared() {
+        let src = "see [[tag/no]]\n\nTags: [[tag/b]], [[tag/a|A]] [[c]]\n\n tags: [[tag/x]]";
+        assert_eq!(tags(&creoles(src)), ["a", "b"]);
+        assert_eq!(tag_name("tag/ a/b "), Some("a/b"));
+        assert_eq!(tag_name("tag/"), None);
+    }
+}
@@ -0,0 +1,177 @@
+//! Table of contents of a page, and the sections its headings start.
+
+use std::collections::HashSet;
+
+use creole_nom::prelude::*;
+use sycamore::prelude::*;
+use web_sys::Event;
+
+use crate::creole_children;
+
+/// Line standing for the table of contents of its page.
+pub const PLACEHOLDER: &str = "<<TOC>>";
+/// Headings from which a page without the placeholder gets a table of contents at the top.
+pub const TOC_MIN: usize = 4;
+
+/// Id of the section with the heading `text`, which links can name by either.
+pub fn slug(text: &str) -> String {
+    let mut rst = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            rst.push(c);
+        } else if !rst.is_empty() && !rst.ends_with('-') {
+            rst.push('-');
+        }
+    }
+    match rst.trim_end_matches('-') {
+        "" => String::from("section"),
+        s => s.to_owned(),
+    }
+}
+
+/// Page and section of a link like `page#section`. Links to `#section` stay on their page.
+pub fn split(href: &str) -> (&str, Option<&str>) {
+    match href.split_once('#') {
+        Some((page, section)) => (page.trim(), Some(section)),
+        None => (href.trim(), None),
+    }
+}
+
+/// Text of heading `items`, without its markup.
+pub fn heading_text(items: &[ICreole]) -> String {
+    let mut rst = String::new();
+    for i in items {
+        match i {
+            ICreole::Text(t) | ICreole::DontFormat(t) => rst.push_str(t),
+            ICreole::Link(href, t) => rst.push_str(if t.is_empty() { href } else { t }),
+            ICreole::Image(_, t) => rst.push_str(t),
+            ICreole::Silentbreak | ICreole::ForceLinebreak => rst.push(' '),
+            i => rst.push_str(&heading_text(creole_children(i))),
+        }
+    }
+    rst
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub level: u8,
+    pub text: String,
+    pub slug: String,
+}
+
+/// Headings of a page. Headings with the same slug as an earlier one get the next free `-2`, `-3`…
+/// so that every section has its own id, and links naming the text lead to the first of them.
+pub fn entries<'a, 'b: 'a>(tree: impl IntoIterator<Item = &'a ICreole<'b>>) -> Vec<Entry> {
+    let mut used = HashSet::new();
+    tree.into_iter()
+        .filter_map(|i| match i {
+            ICreole::Heading(level, items) => {
+                let text = heading_text(items).trim().to_owned();
+                let base = slug(&text);
+                let mut slug = base.clone();
+                for n in 2.. {
+                    if used.insert(slug.clone()) {
+                        break;
+                    }
+                    slug = format!("{base}-{n}");
+                }
+                Some(Entry { level: *level, slug, text })
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `i` is the line standing for the table of contents.
+pub fn is_placeholder(i: &ICreole) -> bool {
+    matches!(i, ICreole::Line(items) if matches!(items.as_slice(), [ICreole::Text(t)] if t.trim() == PLACEHOLDER))
+}
+
+/// Scrolls to the section `id`, keeping it in the location to link to.
+pub fn scroll_to(id: &str) {
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    if let Some(e) = window.document().and_then(|d| d.get_element_by_id(id)) {
+        e.scroll_into_view();
+        if let Ok(history) = window.history() {
+            history.replace_state_with_url(&history.state().unwrap_or_default(), "", Some(&format!("#{id}"))).unwrap_or(());
+        }
+    }
+}
+
+/// Scrolls to the section named in the location, like after following a `[[page#section]]` link.
+pub fn scroll_to_location() {
+    let hash = web_sys::window().and_then(|w| w.location().hash().ok()).unwrap_or_default();
+    if let Some(section) = hash.strip_prefix('#').filter(|s| !s.is_empty()) {
+        scroll_to(&urlencoding::decode(section).map_or_else(|_| section.to_owned(), |s| s.into_owned()));
+    }
+}
+
+/// Link to section `id` of the page shown.
+pub fn section_link<G: Html>(cx: Scope, id: String, text: String) -> View<G> {
+    let href = format!("#{id}");
+    let on_click = move |e: Event| {
+        e.prevent_default();
+        scroll_to(&id);
+    };
+    view! { cx, a(href=href, on:click=on_click) { (text) } }
+}
+
+/// Nested list of `entries`, each one holding the deeper ones after it.
+fn list<G: Html>(cx: Scope, entries: &[Entry]) -> View<G> {
+    let mut items = vec![];
+    let mut i = 0;
+    while i < entries.len() {
+        let e = &entries[i];
+        let end = entries[i + 1..].iter().position(|n| n.level <= e.level).map_or(entries.len(), |p| i + 1 + p);
+        let link = section_link(cx, e.slug.clone(), e.text.clone());
+        let sub = if end > i + 1 { list(cx, &entries[i + 1..end]) } else { view! { cx, } };
+        items.push(view! { cx, li { (link) (sub) } });
+        i = end;
+    }
+    let items = View::new_fragment(items);
+    view! { cx, ol { (items) } }
+}
+
+#[derive(Prop)]
+pub struct TocProps<'a> {
+    entries: &'a ReadSignal<Vec<Entry>>,
+}
+
+#[component]
+pub fn Toc<'a, G: Html>(cx: Scope<'a>, props: TocProps<'a>) -> View<G> {
+    view! { cx,
+      nav(class="toc") {
+        h3 { "Contents" }
+        (list(cx, &props.entries.get()))
+      }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slug(" Editor  Shortcuts! "), "editor-shortcuts");
+        assert_eq!(slug("[[Nowiki]]: 한글"), "nowiki-한글");
+        assert_eq!(slug("--"), "section");
+        assert_eq!(split("a/b#Some Part"), ("a/b", Some("Some Part")));
+        assert_eq!(split("#x"), ("", Some("x")));
+        assert_eq!(split(" a "), ("a", None));
+    }
+
+    #[test]
+    fn headings() {
+        let tree = creoles("= A **b**\n\ntext\n\n== [[c|C]] d\n\n<<TOC>>");
+        let entries = entries(&tree);
+        assert_eq!(entries.iter().map(|e| (e.level, e.slug.as_str())).collect::<Vec<_>>(), [(1, "a-b"), (2, "c-d")]);
+        assert!(tree.iter().any(is_placeholder));
+
+        let tree = creoles("= A\n\n== A\n\n= A 2\n\n= a");
+        let slugs: Vec<String> = super::entries(&tree).into_iter().map(|e| e.slug).collect();
+        assert_eq!(slugs, ["a", "a-2", "a-2-2", "a-3"]);
+    }
+}
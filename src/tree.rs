@@ -0,0 +1,128 @@
+//! Parsed Creole as byte ranges of its source, sent between the parser worker and the page.
+
+use creole_nom::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Start and end byte of a text in the source.
+pub type Span = (usize, usize);
+
+/// `ICreole` with every text replaced by its span.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Node {
+    Text(Span),
+    Line(Vec<Node>),
+    Bold(Vec<Node>),
+    Italic(Vec<Node>),
+    BulletList(Vec<Node>),
+    NumberedList(Vec<Node>),
+    ListItem(Vec<Node>),
+    Link(Span, Span),
+    Heading(u8, Vec<Node>),
+    Silentbreak,
+    ForceLinebreak,
+    HorizontalLine,
+    Image(Span, Span),
+    DontFormat(Span),
+    Table(Vec<Node>),
+    TableHeaderRow(Vec<Node>),
+    TableHeaderCell(Vec<Node>),
+    TableRow(Vec<Node>),
+    TableCell(Vec<Node>),
+}
+
+/// Blocks of source for the worker to parse.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub id: u32,
+    pub blocks: Vec<String>,
+}
+
+/// What the worker sends back. Every request gets a `Parsed` or a `Failed` reply.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Reply {
+    /// The worker is loaded and reads requests from now on. Requests sent before are lost.
+    Ready,
+    /// Trees of the blocks of the request `id`, in the same order.
+    Parsed { id: u32, trees: Vec<Vec<Node>> },
+    /// The request `id` could not be parsed, or could not even be read when there is no `id`.
+    Failed { id: Option<u32>, reason: String },
+}
+
+fn span(src: &str, s: &str) -> Span {
+    // texts that are not part of the source can only be empty
+    match (s.as_ptr() as usize).checked_sub(src.as_ptr() as usize) {
+        Some(start) if start + s.len() <= src.len() => (start, start + s.len()),
+        _ => (0, 0),
+    }
+}
+
+/// `tree` parsed from `src`, as spans of it.
+pub fn encode(src: &str, tree: &[ICreole]) -> Vec<Node> {
+    let nodes = |c: &[ICreole]| encode(src, c);
+    tree.iter()
+        .map(|i| match i {
+            ICreole::Text(t) => Node::Text(span(src, t)),
+            ICreole::Line(c) => Node::Line(nodes(c)),
+            ICreole::Bold(c) => Node::Bold(nodes(c)),
+            ICreole::Italic(c) => Node::Italic(nodes(c)),
+            ICreole::BulletList(c) => Node::BulletList(nodes(c)),
+            ICreole::NumberedList(c) => Node::NumberedList(nodes(c)),
+            ICreole::ListItem(c) => Node::ListItem(nodes(c)),
+            ICreole::Link(h, t) => Node::Link(span(src, h), span(src, t)),
+            ICreole::Heading(l, c) => Node::Heading(*l, nodes(c)),
+            ICreole::Silentbreak => Node::Silentbreak,
+            ICreole::ForceLinebreak => Node::ForceLinebreak,
+            ICreole::HorizontalLine => Node::HorizontalLine,
+            ICreole::Image(s, t) => Node::Image(span(src, s), span(src, t)),
+            ICreole::DontFormat(t) => Node::DontFormat(span(src, t)),
+            ICreole::Table(c) => Node::Table(nodes(c)),
+            ICreole::TableHeaderRow(c) => Node::TableHeaderRow(nodes(c)),
+            ICreole::TableHeaderCell(c) => Node::TableHeaderCell(nodes(c)),
+            ICreole::TableRow(c) => Node::TableRow(nodes(c)),
+            ICreole::TableCell(c) => Node::TableCell(nodes(c)),
+        })
+        .collect()
+}
+
+/// The tree `encode` made of `src`, borrowing from `src` again.
+pub fn decode<'a>(src: &'a str, tree: &[Node]) -> Vec<ICreole<'a>> {
+    let text = |s: &Span| src.get(s.0..s.1).unwrap_or("");
+    let items = |c: &[Node]| decode(src, c);
+    tree.iter()
+        .map(|n| match n {
+            Node::Text(t) => ICreole::Text(text(t)),
+            Node::Line(c) => ICreole::Line(items(c)),
+            Node::Bold(c) => ICreole::Bold(items(c)),
+            Node::Italic(c) => ICreole::Italic(items(c)),
+            Node::BulletList(c) => ICreole::BulletList(items(c)),
+            Node::NumberedList(c) => ICreole::NumberedList(items(c)),
+            Node::ListItem(c) => ICreole::ListItem(items(c)),
+            Node::Link(h, t) => ICreole::Link(text(h), text(t)),
+            Node::Heading(l, c) => ICreole::Heading(*l, items(c)),
+            Node::Silentbreak => ICreole::Silentbreak,
+            Node::ForceLinebreak => ICreole::ForceLinebreak,
+            Node::HorizontalLine => ICreole::HorizontalLine,
+            Node::Image(s, t) => ICreole::Image(text(s), text(t)),
+            Node::DontFormat(t) => ICreole::DontFormat(text(t)),
+            Node::Table(c) => ICreole::Table(items(c)),
+            Node::TableHeaderRow(c) => ICreole::TableHeaderRow(items(c)),
+            Node::TableHeaderCell(c) => ICreole::TableHeaderCell(items(c)),
+            Node::TableRow(c) => ICreole::TableRow(items(c)),
+            Node::TableCell(c) => ICreole::TableCell(items(c)),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let src = "= t\n**b //i//** [[a|b]] {{c.png|d}}\n* x\n** y\n|=h|\n|{{{n}}}|\n----\nl\\\\m";
+        let tree = creoles(src);
+        let json = serde_json::to_string(&encode(src, &tree)).unwrap();
+        let copy = String::from(src);
+        assert_eq!(decode(&copy, &serde_json::from_str::<Vec<Node>>(&json).unwrap()), tree);
+    }
+}
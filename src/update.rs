@@ -0,0 +1,100 @@
+//! Registers the service worker and finds new versions of the app waiting for it.
+
+use sycamore::prelude::*;
+use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ServiceWorker, ServiceWorkerRegistration, ServiceWorkerState};
+
+use crate::AppState;
+
+const SCRIPT: &str = "/service-worker.js";
+/// Asks a waiting service worker to take over, see `service-worker/src/lib.rs`.
+const SKIP_WAITING: &str = "skip-waiting";
+
+/// Registers the service worker, and sets `waiting` to new versions of it once they are installed.
+pub fn register(waiting: RcSignal<Option<ServiceWorker>>) {
+    let window = web_sys::window().expect("no global `window` exists");
+    let navigator = window.navigator();
+    // missing outside of secure contexts
+    if !js_sys::Reflect::has(&navigator, &"serviceWorker".into()).unwrap_or(false) {
+        return;
+    }
+    let container = navigator.service_worker();
+    // the first worker of a browser controls nothing, so there is nothing to update then
+    let reloads = container.controller().is_some();
+    wasm_bindgen_futures::spawn_local(async move {
+        let registration: ServiceWorkerRegistration = match JsFuture::from(container.register(SCRIPT)).await {
+            Ok(r) => r.unchecked_into(),
+            Err(e) => {
+                log::error!("could not register the service worker : {:?}", e);
+                return;
+            }
+        };
+        if !reloads {
+            return;
+        }
+        if let Some(w) = registration.waiting() {
+            waiting.set(Some(w));
+        }
+        let on_update = {
+            let registration = registration.clone();
+            Closure::<dyn Fn()>::new(move || {
+                let worker = match registration.installing() {
+                    Some(w) => w,
+                    None => return,
+                };
+                let on_state = {
+                    let (worker, waiting) = (worker.clone(), waiting.clone());
+                    Closure::<dyn Fn()>::new(move || {
+                        if worker.state() == ServiceWorkerState::Installed {
+                            waiting.set(Some(worker.clone()));
+                        }
+                    })
+                };
+                worker.set_onstatechange(Some(on_state.as_ref().unchecked_ref()));
+                on_state.forget();
+            })
+        };
+        registration.set_onupdatefound(Some(on_update.as_ref().unchecked_ref()));
+        on_update.forget();
+        // the page is reloaded once the new worker took over
+        let on_controller = Closure::<dyn Fn()>::new(|| {
+            web_sys::window().expect("no global `window` exists").location().reload().unwrap_or(());
+        });
+        container.set_oncontrollerchange(Some(on_controller.as_ref().unchecked_ref()));
+        on_controller.forget();
+    });
+}
+
+/// Offers to reload into a new version of the app, once every edit is saved.
+#[component]
+pub fn UpdateBanner<G: Html>(cx: Scope) -> View<G> {
+    let state = use_context::<AppState>(cx);
+    let requested = create_signal(cx, false);
+    create_effect(cx, move || {
+        if *requested.get() && !*state.unsaved.get() {
+            if let Some(w) = state.waiting.get().as_ref() {
+                w.post_message(&JsValue::from_str(SKIP_WAITING)).unwrap_or(());
+            }
+        }
+    });
+
+    view! { cx,
+      ( if state.waiting.get().is_some() {
+          view! { cx,
+            div(class="update-banner") {
+              ( if *requested.get() {
+                  "Reloading once your edits are saved..."
+                } else {
+                  "A new version of the app is available."
+                }
+              )
+              button(on:click=|_| requested.set(true), disabled=*requested.get()) { "Reload" }
+            }
+          }
+        } else {
+          view! { cx, }
+        }
+      )
+    }
+}
@@ -0,0 +1,119 @@
+//! Minimal writer for uncompressed (stored) zip archives.
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+#[derive(Default)]
+pub struct Zip {
+    data: Vec<u8>,
+    entries: Vec<Entry>,
+}
+
+/// Version 2.0, general purpose flag with UTF-8 names, stored, 1980-01-01 00:00.
+const HEADER: [u16; 5] = [20, 1 << 11, 0, 0, (1 << 5) | 1];
+
+impl Zip {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, content: &[u8]) {
+        let entry = Entry {
+            name: name.to_string(),
+            crc: crc32(content),
+            size: content.len() as u32,
+            offset: self.data.len() as u32,
+        };
+        self.data.extend(0x0403_4b50u32.to_le_bytes());
+        for h in HEADER {
+            self.data.extend(h.to_le_bytes());
+        }
+        for v in [entry.crc, entry.size, entry.size] {
+            self.data.extend(v.to_le_bytes());
+        }
+        self.data.extend((entry.name.len() as u16).to_le_bytes());
+        self.data.extend(0u16.to_le_bytes());
+        self.data.extend(entry.name.as_bytes());
+        self.data.extend(content);
+        self.entries.push(entry);
+    }
+
+    pub fn finish(mut self) -> Vec<u8> {
+        let start = self.data.len() as u32;
+        for e in &self.entries {
+            self.data.extend(0x0201_4b50u32.to_le_bytes());
+            self.data.extend(20u16.to_le_bytes());
+            for h in HEADER {
+                self.data.extend(h.to_le_bytes());
+            }
+            for v in [e.crc, e.size, e.size] {
+                self.data.extend(v.to_le_bytes());
+            }
+            // name length, extra, comment, disk, internal attributes
+            for v in [e.name.len() as u16, 0, 0, 0, 0] {
+                self.data.extend(v.to_le_bytes());
+            }
+            self.data.extend(0u32.to_le_bytes());
+            self.data.extend(e.offset.to_le_bytes());
+            self.data.extend(e.name.as_bytes());
+        }
+        let size = self.data.len() as u32 - start;
+        let count = self.entries.len() as u16;
+        self.data.extend(0x0605_4b50u32.to_le_bytes());
+        for v in [0, 0, count, count] {
+            self.data.extend(v.to_le_bytes());
+        }
+        self.data.extend(size.to_le_bytes());
+        self.data.extend(start.to_le_bytes());
+        self.data.extend(0u16.to_le_bytes());
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"The quick brown fox jumps over the lazy dog"), 0x414F_A339);
+    }
+
+    #[test]
+    fn archive() {
+        let mut zip = Zip::new();
+        zip.add("a/b.html", b"123456789");
+        let data = zip.finish();
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        let u16_at = |i: usize| u16::from_le_bytes(data[i..i + 2].try_into().unwrap());
+
+        // local header, then the name and content
+        assert_eq!(u32_at(0), 0x0403_4b50);
+        assert_eq!((u32_at(14), u32_at(18), u32_at(22)), (0xCBF4_3926, 9, 9));
+        assert_eq!(&data[30..38], b"a/b.html");
+        assert_eq!(&data[38..47], b"123456789");
+        // central directory, then its end pointing back to it
+        assert_eq!(u32_at(47), 0x0201_4b50);
+        let end = data.len() - 22;
+        assert_eq!(u32_at(end), 0x0605_4b50);
+        assert_eq!((u16_at(end + 8), u16_at(end + 10)), (1, 1));
+        assert_eq!((u32_at(end + 12), u32_at(end + 16)), (end as u32 - 47, 47));
+    }
+}
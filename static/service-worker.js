@@ -1,39 +1,22 @@
-var cacheName = 'chideas-pwaa-v1.0.0';
-var files = [
-  '/',
-];
-
-self.addEventListener('activate', event => {
-  const cacheWhitelist = [cacheName];
-  event.waitUntil(
-    caches.keys().then(cacheNames => {
-      return Promise.all(
-        cacheNames.map(cacheName => {
-          if (cacheWhitelist.indexOf(cacheName) === -1) {
-            return caches.delete(cacheName);
-          }
-        })
-      );
-    })
-  );
-});
-
-/* Start the service worker and cache all of the app's content */
-self.addEventListener('install', e => {
-  e.waitUntil(
-    caches.open(cacheName).then(cache => {
-      return cache.addAll(files);
-    })
-  );
-});
-
-/* Serve cached content when offline */
-self.addEventListener('fetch', e => {
-  e.respondWith(
-    caches.match(e.request).then(response => {
-      return response || fetch(e.request);
-    }).catch(e => {
-      // offline without cache
-    })
-  );
-});
\ No newline at end of file
+/* Registers the handlers of the Rust service worker in `service-worker/`.
+   They have to be added while this script runs, before its module is loaded. */
+importScripts('./precache.js');
+importScripts(self.PRECACHE.worker[0]);
+
+const ready = wasm_bindgen(self.PRECACHE.worker[1]);
+
+self.addEventListener('install', e => {
+  e.waitUntil(ready.then(() => wasm_bindgen.on_install(e)));
+});
+
+self.addEventListener('activate', e => {
+  e.waitUntil(ready.then(() => wasm_bindgen.on_activate(e)));
+});
+
+self.addEventListener('fetch', e => {
+  e.respondWith(ready.then(() => wasm_bindgen.on_fetch(e)));
+});
+
+self.addEventListener('message', e => {
+  e.waitUntil(ready.then(() => wasm_bindgen.on_message(e)));
+});